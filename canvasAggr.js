@@ -1,9 +1,89 @@
+import { showToast, playAlertSound } from "./toast.js";
+import { formatLargeNumber, formatQuantity } from "./format.js";
+import { endpoints } from "./endpoints.js";
+
 let currentSymbol;
 let histTrades = [];
 
+// Binance Futures request weight allowed per minute per IP
+const BINANCE_WEIGHT_LIMIT = 2400;
+// weight of one aggTrades request, counted locally when the header is hidden
+const AGG_TRADES_WEIGHT = 20;
+// minimum time between large trade alerts, prints in between are counted
+const LARGE_TRADE_COOLDOWN = 5000;
+// time for the rolling orderbook max to decay by half
+const ROLLING_MAX_HALF_LIFE = 30000;
+// retracement ratios drawn by the fib tool, picked in the drawing menu
+const FIB_LEVEL_SETS = {
+  standard: [0, 0.236, 0.382, 0.5, 0.618, 0.786, 1],
+  key: [0, 0.382, 0.5, 0.618, 1],
+  extended: [0, 0.236, 0.382, 0.5, 0.618, 0.786, 1, 1.272, 1.618],
+};
+
+// snaps a price to its bucket, the epsilon keeps 0.3 / 0.1 from flooring to 2
+function roundToBucket(price, bucketSize, mode = "nearest") {
+  const steps = price / bucketSize;
+  if (mode === "floor") {
+    return Math.floor(steps + 1e-9) * bucketSize;
+  } else if (mode === "ceil") {
+    return Math.ceil(steps - 1e-9) * bucketSize;
+  }
+  return Math.round(steps) * bucketSize;
+}
+
+// inline text input over the main chart, submits the trimmed text on enter
+function openTextEditor(x, y, initialText, onSubmit) {
+  const editor = document.createElement("input");
+  editor.id = "annotation-editor";
+  editor.type = "text";
+  editor.value = initialText;
+  editor.style.left = `${x}px`;
+  editor.style.top = `${y}px`;
+  document.querySelector("#left-wrapper").appendChild(editor);
+  editor.focus();
+
+  let closed = false;
+  const close = (submit) => {
+    if (closed) return;
+    closed = true;
+    editor.remove();
+    if (submit) onSubmit(editor.value.trim());
+  };
+  editor.addEventListener("keydown", (event) => {
+    if (event.key === "Enter") close(true);
+    else if (event.key === "Escape") close(false);
+  });
+  editor.addEventListener("blur", () => close(false));
+}
+
+// drawings are saved per symbol, an unreadable entry starts over empty
+function loadDrawings(key) {
+  try {
+    return JSON.parse(localStorage.getItem(key) ?? "{}");
+  } catch (error) {
+    console.error(`Ignoring unreadable saved ${key}:`, error);
+    return {};
+  }
+}
+
+function hexToRgba(hex, alpha) {
+  const r = parseInt(hex.slice(1, 3), 16);
+  const g = parseInt(hex.slice(3, 5), 16);
+  const b = parseInt(hex.slice(5, 7), 16);
+  return `rgba(${r}, ${g}, ${b}, ${alpha})`;
+}
+
 export class CanvasController {
   zoomYLevel = 0.2222;
   zoomXLevel = 0;
+  colors = { buy: "#51CDA0", sell: "#C0504E" };
+  priceScaleMode = "price";
+  anchorPrice;
+  activeTool = null;
+  annotationFontSize = 12;
+  #annotations = loadDrawings("annotations");
+  #fibs = loadDrawings("fibs");
+  #fibDrag = null;
   #canvas1;
   #canvas1_Overlay;
   #canvas2;
@@ -21,10 +101,27 @@ export class CanvasController {
   #autoScaleBtn;
   #cvdBtnActive = true;
   #oiBtnActive = true;
+  #lsBtnActive = false;
+  #spreadBtnActive = false;
   #isAnimationFrameRequested = false;
   #canvasStarted = false;
   #gettingHistKlines = false;
   #gettingHistTrades = false;
+  #usedWeight = 0;
+  #localWeight = 0;
+  #localWeightMinute = 0;
+  #throttledUntil = 0;
+  histTradesConcurrency = 1;
+  histTradesBatchSize = 1000;
+  followMode = "always";
+  largeTradeThreshold = 0;
+  #largeTradeAlertAt = 0;
+  #skippedLargeTrades = 0;
+  labelFontSize = 11;
+  labelFontFamily = "monospace";
+  fibLevels = FIB_LEVEL_SETS.standard;
+  klineRetention = 720;
+  roundingMode = "nearest";
   constructor(canvasObjects) {
     this.#canvas1 = new Canvas1(
       this,
@@ -72,11 +169,30 @@ export class CanvasController {
 
     // Panning
     this.#canvas1_Overlay.canvas.addEventListener("mousedown", (event) => {
+      if (this.activeTool) return;
+      // grabbing a fib anchor moves that anchor instead of the chart
+      if (this.#canvasStarted) {
+        const { x, y } = this.#overlayPoint(event);
+        this.#fibDrag = this.#canvas1.fibAnchorAt(x, y);
+        if (this.#fibDrag) return;
+      }
       this.#isDragging = true;
       this.#initialMousePos = { x: event.clientX, y: event.clientY };
     });
     this.#canvas1_Overlay.canvas.addEventListener("mousemove", (event) => {
-      if (this.#isDragging) {
+      if (this.#fibDrag) {
+        const { x, y } = this.#overlayPoint(event);
+        const { fib, end } = this.#fibDrag;
+        fib[end] = this.#canvas1.toChartPoint(x, y);
+
+        if (!this.#isAnimationFrameRequested) {
+          this.#isAnimationFrameRequested = true;
+          requestAnimationFrame(() => {
+            this.#canvas1.updateData(this.#kline, []);
+            this.#isAnimationFrameRequested = false;
+          });
+        }
+      } else if (this.#isDragging) {
         this.#autoScale = false;
 
         let currentMousePos = { x: event.clientX, y: event.clientY };
@@ -104,10 +220,13 @@ export class CanvasController {
       }
     });
     ["mouseup", "mouseleave"].forEach((event) =>
-      this.#canvas1_Overlay.canvas.addEventListener(
-        event,
-        () => (this.#isDragging = false)
-      )
+      this.#canvas1_Overlay.canvas.addEventListener(event, () => {
+        this.#isDragging = false;
+        if (this.#fibDrag) {
+          this.#fibDrag = null;
+          this.#saveDrawings("fibs", this.#fibs);
+        }
+      })
     );
     // Zoom Main
     this.#canvas1_Overlay.canvas.addEventListener("wheel", (event) => {
@@ -201,12 +320,110 @@ export class CanvasController {
       const rect = this.#canvas1_Overlay.canvas.getBoundingClientRect();
       const x = event.clientX - rect.left;
       const y = event.clientY - rect.top;
+      if (this.#canvas1_Overlay.ruler) {
+        this.#canvas1_Overlay.ruler = this.#measure(
+          this.#canvas1_Overlay.ruler,
+          { x, y }
+        );
+      }
       this.#canvas1_Overlay.updateCrosshair(x, y);
     });
     this.#canvas1_Overlay.canvas.addEventListener("mouseleave", () =>
       this.#canvas1_Overlay.clearCrosshair()
     );
 
+    // Annotations
+    this.#canvas1_Overlay.canvas.addEventListener("click", (event) => {
+      if (this.activeTool !== "text" || !this.#canvasStarted) return;
+
+      const { x, y } = this.#overlayPoint(event);
+      const point = this.#canvas1.toChartPoint(x, y);
+      const fontSize = this.annotationFontSize;
+      this.setTool(null);
+
+      openTextEditor(x, y - fontSize, "", (text) => {
+        if (!text) return;
+        this.#canvas1.annotations.push({ ...point, text, fontSize });
+        this.#saveDrawings("annotations", this.#annotations);
+      });
+    });
+    // Ruler and fib retracement, both dragged out with the ruler preview
+    this.#canvas1_Overlay.canvas.addEventListener("mousedown", (event) => {
+      if (!["ruler", "fib"].includes(this.activeTool) || !this.#canvasStarted)
+        return;
+
+      const start = this.#overlayPoint(event);
+      this.#canvas1_Overlay.ruler = this.#measure(
+        { tool: this.activeTool, start },
+        start
+      );
+    });
+    ["mouseup", "mouseleave"].forEach((event) =>
+      this.#canvas1_Overlay.canvas.addEventListener(event, () => {
+        const ruler = this.#canvas1_Overlay.ruler;
+        if (!ruler) return;
+
+        if (ruler.tool === "fib" && ruler.start.y !== ruler.end.y) {
+          this.#canvas1.fibs.push({
+            from: this.#canvas1.toChartPoint(ruler.start.x, ruler.start.y),
+            to: this.#canvas1.toChartPoint(ruler.end.x, ruler.end.y),
+          });
+          this.#saveDrawings("fibs", this.#fibs);
+        }
+        this.#canvas1_Overlay.ruler = null;
+        this.#canvas1_Overlay.clearCrosshair();
+        this.setTool(null);
+      })
+    );
+    this.#canvas1_Overlay.canvas.addEventListener("dblclick", (event) => {
+      const { x, y } = this.#overlayPoint(event);
+      const annotation = this.#canvas1.annotationAt(x, y);
+      if (!annotation) {
+        // double clicking a fib level removes that retracement
+        const fib = this.#canvas1.fibAt(x, y);
+        if (fib) {
+          this.#canvas1.fibs.splice(this.#canvas1.fibs.indexOf(fib), 1);
+          this.#saveDrawings("fibs", this.#fibs);
+        }
+        return;
+      }
+
+      // submitting an empty label removes it
+      openTextEditor(x, y, annotation.text, (text) => {
+        const annotations = this.#canvas1.annotations;
+        if (text) {
+          annotation.text = text;
+        } else {
+          annotations.splice(annotations.indexOf(annotation), 1);
+        }
+        this.#saveDrawings("annotations", this.#annotations);
+      });
+    });
+    document.querySelectorAll(".js-tool-btn").forEach((button) => {
+      button.addEventListener("click", () => {
+        this.setTool(
+          this.activeTool === button.dataset.tool ? null : button.dataset.tool
+        );
+      });
+    });
+    const fibLevelsBtn = document.querySelector("#fib-levels-select");
+    fibLevelsBtn.addEventListener("change", (event) => {
+      this.fibLevels = FIB_LEVEL_SETS[fibLevelsBtn.value];
+    });
+    const annotationSizeBtn = document.querySelector(
+      "#annotation-size-select"
+    );
+    annotationSizeBtn.addEventListener("change", (event) => {
+      this.annotationFontSize = Number(annotationSizeBtn.value);
+    });
+
+    // Jump to extremes
+    ["high", "low"].forEach((kind) => {
+      document
+        .querySelector(`#jump-${kind}-btn`)
+        .addEventListener("click", () => this.jumpToExtreme(kind));
+    });
+
     // CVD and OI buttons
     document.querySelectorAll("#top-nav button").forEach((button) => {
       button.addEventListener(
@@ -229,9 +446,31 @@ export class CanvasController {
             clickedButton.classList.toggle("disabled");
             this.#oiBtnActive = !this.#oiBtnActive;
             this.#canvas4.toggleIndicator("oi", this.#oiBtnActive);
+          } else if (clickedButton.id === "lsToggleBtn") {
+            clickedButton.classList.toggle("disabled");
+            this.#lsBtnActive = !this.#lsBtnActive;
+            this.#canvas4.toggleIndicator("ls", this.#lsBtnActive);
+          } else if (clickedButton.id === "spreadToggleBtn") {
+            clickedButton.classList.toggle("disabled");
+            this.#spreadBtnActive = !this.#spreadBtnActive;
+            this.#canvas4.toggleIndicator("spread", this.#spreadBtnActive);
+          } else if (
+            clickedButton.id === "markToggleBtn" ||
+            clickedButton.id === "indexToggleBtn"
+          ) {
+            clickedButton.classList.toggle("disabled");
+            const line =
+              clickedButton.id === "markToggleBtn" ? "mark" : "index";
+            this.#canvas1.referenceLines[line] =
+              !this.#canvas1.referenceLines[line];
           }
 
-          if (!this.#cvdBtnActive && !this.#oiBtnActive) {
+          if (
+            !this.#cvdBtnActive &&
+            !this.#oiBtnActive &&
+            !this.#lsBtnActive &&
+            !this.#spreadBtnActive
+          ) {
             document.querySelector("#canvas4").style.display = "none";
             document.querySelector("#canvas1").style.height = "90%";
             document.querySelector("#canvas2").style.height = "90%";
@@ -264,6 +503,7 @@ export class CanvasController {
     });
     // Tick size
     const tickSizeBtn = document.querySelector("#ticksize-select");
+    const groupingBtn = document.querySelector("#orderbook-grouping-select");
     tickSizeBtn.addEventListener("change", (event) => {
       const calculatedValue = this.#tickSize * tickSizeBtn.value;
       console.log("new tick size:", calculatedValue);
@@ -272,15 +512,187 @@ export class CanvasController {
       this.#canvas1_Overlay.bucketSize = calculatedValue;
       this.#canvas1.maxQty = 0;
 
-      this.#canvas2.bucketSize = calculatedValue;
+      this.#canvas2.bucketSize =
+        this.#tickSize * (Number(groupingBtn.value) || tickSizeBtn.value);
       this.#canvas2_Overlay.bucketSize = calculatedValue;
     });
+    // Orderbook grouping, 0 follows the chart tick size
+    groupingBtn.addEventListener("change", (event) => {
+      this.#canvas2.bucketSize =
+        this.#tickSize * (Number(groupingBtn.value) || tickSizeBtn.value);
+    });
+    // Price to tick rounding
+    const roundingBtn = document.querySelector("#rounding-select");
+    roundingBtn.addEventListener("change", (event) => {
+      this.roundingMode = roundingBtn.value;
+    });
+    // Orderbook wide spread hold
+    const maxSpreadBtn = document.querySelector("#max-spread-select");
+    maxSpreadBtn.addEventListener("change", (event) => {
+      this.#canvas2.maxSpreadPct = Number(maxSpreadBtn.value);
+    });
+    // Orderbook minimum size
+    const minSizeBtn = document.querySelector("#min-size-select");
+    minSizeBtn.addEventListener("change", (event) => {
+      this.#canvas2.minOrderSize = Number(minSizeBtn.value);
+    });
+    // Price scale mode
+    const priceScaleBtn = document.querySelector("#price-scale-select");
+    priceScaleBtn.addEventListener("change", (event) => {
+      this.priceScaleMode = priceScaleBtn.value;
+    });
+    // Orderbook scale normalization
+    const orderbookScaleBtn = document.querySelector("#orderbook-scale-select");
+    orderbookScaleBtn.addEventListener("change", (event) => {
+      this.#canvas2.scaleMode = orderbookScaleBtn.value;
+    });
+    // Chart label font
+    const fontSizeBtn = document.querySelector("#font-size-select");
+    fontSizeBtn.addEventListener("change", (event) => {
+      this.labelFontSize = Number(fontSizeBtn.value);
+    });
+    const fontFamilyBtn = document.querySelector("#font-family-select");
+    fontFamilyBtn.addEventListener("change", (event) => {
+      this.labelFontFamily = fontFamilyBtn.value;
+    });
+    // Large trade alert
+    const largeTradeBtn = document.querySelector("#large-trade-select");
+    largeTradeBtn.addEventListener("change", (event) => {
+      this.largeTradeThreshold = Number(largeTradeBtn.value);
+    });
+    // Follow new klines
+    const followBtn = document.querySelector("#follow-select");
+    followBtn.addEventListener("change", (event) => {
+      this.followMode = followBtn.value;
+    });
+    // Footprint studies trade minimum
+    const minStudyTradesBtn = document.querySelector(
+      "#min-study-trades-select"
+    );
+    minStudyTradesBtn.addEventListener("change", (event) => {
+      this.#canvas1.minStudyTrades = Number(minStudyTradesBtn.value);
+    });
+    // Footprint POC line
+    const pocLineBtn = document.querySelector("#poc-line-toggle");
+    pocLineBtn.addEventListener("change", (event) => {
+      this.#canvas1.showPocLine = pocLineBtn.checked;
+    });
+    const pocColorInput = document.querySelector("#poc-color");
+    pocColorInput.addEventListener("input", () => {
+      this.#canvas1.pocColor = pocColorInput.value;
+    });
+    // Footprint value area
+    const valueAreaBtn = document.querySelector("#value-area-select");
+    valueAreaBtn.addEventListener("change", (event) => {
+      this.#canvas1.valueAreaPct = Number(valueAreaBtn.value);
+    });
+    // Kline wicks
+    const hideWicksBtn = document.querySelector("#hide-wicks-select");
+    hideWicksBtn.addEventListener("change", (event) => {
+      this.#canvas1.hideWicksBelow = Number(hideWicksBtn.value);
+    });
+    // Delta body coloring
+    const deltaBodyBtn = document.querySelector("#delta-body-toggle");
+    deltaBodyBtn.addEventListener("change", (event) => {
+      this.#canvas1.deltaBodyColor = deltaBodyBtn.checked;
+    });
+    // Footprint trade counts
+    const tradeCountBtn = document.querySelector("#trade-count-toggle");
+    tradeCountBtn.addEventListener("change", (event) => {
+      this.#canvas1.showTradeCounts = tradeCountBtn.checked;
+    });
+    // Live kline marking
+    const liveKlineBtn = document.querySelector("#live-kline-toggle");
+    liveKlineBtn.addEventListener("change", (event) => {
+      this.#canvas1.markLiveKline = liveKlineBtn.checked;
+    });
+    // Break-even offset
+    const breakEvenBtn = document.querySelector("#break-even-select");
+    breakEvenBtn.addEventListener("change", (event) => {
+      this.#canvas1.breakEvenBps = Number(breakEvenBtn.value);
+    });
+    // Last price line
+    const lastPriceStyleBtn = document.querySelector(
+      "#last-price-style-select"
+    );
+    lastPriceStyleBtn.addEventListener("change", (event) => {
+      this.#canvas1.lastPriceLine.style = lastPriceStyleBtn.value;
+    });
+    const lastPriceWidthBtn = document.querySelector(
+      "#last-price-width-select"
+    );
+    lastPriceWidthBtn.addEventListener("change", (event) => {
+      this.#canvas1.lastPriceLine.width = Number(lastPriceWidthBtn.value);
+    });
+    const lastPriceColorInput = document.querySelector("#last-price-color");
+    lastPriceColorInput.addEventListener("input", () => {
+      this.#canvas1.lastPriceLine.color = lastPriceColorInput.value;
+    });
+    // Kline retention
+    const retentionBtn = document.querySelector("#retention-select");
+    retentionBtn.addEventListener("change", (event) => {
+      this.klineRetention = Number(retentionBtn.value);
+    });
+    // Historical trades fetching
+    const fetchWorkersBtn = document.querySelector("#fetch-workers-select");
+    fetchWorkersBtn.addEventListener("change", (event) => {
+      this.histTradesConcurrency = Number(fetchWorkersBtn.value);
+    });
+    const fetchBatchBtn = document.querySelector("#fetch-batch-select");
+    fetchBatchBtn.addEventListener("change", (event) => {
+      this.histTradesBatchSize = Number(fetchBatchBtn.value);
+    });
+    // Footprint percentile highlight
+    const highlightPctBtn = document.querySelector(
+      "#highlight-percentile-select"
+    );
+    highlightPctBtn.addEventListener("change", (event) => {
+      this.#canvas1.highlightPercentile = Number(highlightPctBtn.value);
+    });
+    // Footprint top levels highlight
+    const highlightTopBtn = document.querySelector("#highlight-top-select");
+    highlightTopBtn.addEventListener("change", (event) => {
+      this.#canvas1.highlightTopN = Number(highlightTopBtn.value);
+    });
+    // Zero volume klines
+    const zeroVolumeBtn = document.querySelector("#zero-volume-toggle");
+    zeroVolumeBtn.addEventListener("change", (event) => {
+      this.#canvas3.markZeroVolume = zeroVolumeBtn.checked;
+    });
+    // Volume bars
+    const volumeModeBtn = document.querySelector("#volume-mode-select");
+    volumeModeBtn.addEventListener("change", (event) => {
+      this.#canvas3.volumeMode = volumeModeBtn.value;
+    });
+    // Candle close countdown
+    const countdownToggle = document.querySelector("#countdown-toggle");
+    countdownToggle.addEventListener("change", (event) => {
+      this.#canvas2.showCountdown = countdownToggle.checked;
+    });
+    // Open interest alert
+    const oiAlertBtn = document.querySelector("#oi-alert-select");
+    const oiAlertWindowBtn = document.querySelector("#oi-alert-window-select");
+    [oiAlertBtn, oiAlertWindowBtn].forEach((button) =>
+      button.addEventListener("change", (event) => {
+        this.#canvas4.oiAlert = {
+          threshold: Number(oiAlertBtn.value),
+          window: Number(oiAlertWindowBtn.value),
+        };
+      })
+    );
+    // Trade side colors
+    ["buy", "sell"].forEach((side) => {
+      const colorInput = document.querySelector(`#${side}-color`);
+      colorInput.addEventListener("input", () => {
+        this.colors[side] = colorInput.value;
+      });
+    });
   }
   async fetchHistKlines(symbol, interval, startTime, endTime, limit) {
     this.#gettingHistKlines = true;
     try {
       const response = await fetch(
-        `https://fapi.binance.com/fapi/v1/klines?symbol=${symbol}&interval=${interval}${
+        `${endpoints.rest}/fapi/v1/klines?symbol=${symbol}&interval=${interval}${
           startTime ? "&startTime=" + startTime : ""
         }${endTime ? "&endTime=" + endTime : ""}&limit=${limit}`
       );
@@ -297,27 +709,48 @@ export class CanvasController {
       return;
     }
     this.#gettingHistKlines = true;
-    this.fetchHistKlines(currentSymbol, "1m", startTime, endTime, limit).then(
+    const symbol = currentSymbol;
+    this.fetchHistKlines(symbol, "1m", startTime, endTime, limit).then(
       (data) => {
+        this.#gettingHistKlines = false;
+        // the chart moved on to another symbol, its own fetch comes next
+        if (symbol !== currentSymbol) {
+          console.log("dropped historical klines for", symbol);
+          return;
+        }
         this.#canvas1.resolveHistData("klines", data);
         this.#canvas3.resolveHistData("klines", data);
-        this.#gettingHistKlines = false;
       }
     );
   }
   async fetchHistTrades(symbol, startTime, endTime, limit, retryCount = 0) {
     try {
-      const url = `https://fapi.binance.com/fapi/v1/aggTrades?symbol=${symbol}${
+      const url = `${endpoints.rest}/fapi/v1/aggTrades?symbol=${symbol}${
         startTime ? "&startTime=" + startTime : ""
       }${endTime ? "&endTime=" + endTime : ""}${
         limit ? "&limit=" + limit : ""
       }`;
       const response = await fetch(url);
-      if (response.status === 429) {
-        const waitTime = Math.pow(2, retryCount) * 1000;
+      this.#usedWeight =
+        Number(response.headers.get("x-mbx-used-weight-1m")) ||
+        this.#usedWeight;
+
+      if (response.status === 429 || response.status === 418) {
+        const retryAfter = Number(response.headers.get("retry-after"));
+        const waitTime = retryAfter
+          ? retryAfter * 1000
+          : Math.pow(2, retryCount) * 1000;
         console.log(
           `Rate limit exceeded, pausing for ${waitTime / 1000} seconds...`
         );
+        this.#throttledUntil = Date.now() + waitTime;
+        this.updateThrottleInfo();
+        showToast(
+          response.status === 418
+            ? `Binance temporarily banned this IP for exceeding rate limits, historical trades resume in ${waitTime / 1000}s`
+            : `Binance rate limit reached, historical trades resume in ${waitTime / 1000}s`,
+          "warn"
+        );
         await new Promise((resolve) => setTimeout(resolve, waitTime));
         return this.fetchHistTrades(
           symbol,
@@ -327,6 +760,8 @@ export class CanvasController {
           retryCount + 1
         );
       }
+      this.updateThrottleInfo();
+
       const data = await response.json();
       const trades = data.map((trade) => {
         return {
@@ -343,6 +778,146 @@ export class CanvasController {
       return NaN;
     }
   }
+  integrityReport() {
+    return this.#canvasStarted ? this.#canvas1.integrityReport() : [];
+  }
+  jumpToExtreme(kind) {
+    if (!this.#canvasStarted) return;
+    const extreme = this.#canvas1.findExtreme(kind);
+    if (!extreme) return;
+
+    this.#autoScale = false;
+
+    const { dx, dy } = this.#canvas1.offsetToCenter(extreme);
+    this.#canvas1.panXY(dx, dy);
+    this.#canvas2.panY(dy);
+    this.#canvas3.panX(dx);
+    this.#canvas4.panX(dx);
+    this.#canvas1.flashMarker(extreme);
+
+    this.#canvas1.updateData(this.#kline, []);
+    this.#canvas2.updateData(this.#kline, this.#depth);
+    this.#canvas3.updateData(this.#kline);
+    this.#canvas4.updateData(this.#kline, []);
+
+    this.updateScaleBtn();
+  }
+  // alerts on the largest trade above the notional threshold, at most once
+  // per cooldown, the ones left out are counted into the next alert
+  checkLargeTrades(trades) {
+    if (!this.largeTradeThreshold) return;
+
+    const large = trades.filter(
+      (trade) => trade.q * trade.y >= this.largeTradeThreshold
+    );
+    if (large.length === 0) return;
+
+    if (Date.now() - this.#largeTradeAlertAt < LARGE_TRADE_COOLDOWN) {
+      this.#skippedLargeTrades += large.length;
+      return;
+    }
+    const largest = large.reduce((max, trade) =>
+      trade.q * trade.y > max.q * max.y ? trade : max
+    );
+    const notional = largest.q * largest.y;
+    const others = this.#skippedLargeTrades + large.length - 1;
+    this.#largeTradeAlertAt = Date.now();
+    this.#skippedLargeTrades = 0;
+
+    this.notify(
+      `large ${largest.m ? "sell" : "buy"} ${formatQuantity(largest.q)} @ ${
+        largest.y
+      } ($${formatLargeNumber(Math.round(notional))})` +
+        (others > 0 ? `, ${others} more since the last alert` : ""),
+      "warn"
+    );
+  }
+  labelFont(sizeOffset = 0) {
+    return `${this.labelFontSize + sizeOffset}px ${this.labelFontFamily}`;
+  }
+  notify(message, level = "info") {
+    showToast(message, level);
+    if (document.querySelector("#alert-sound-toggle").checked) {
+      playAlertSound();
+    }
+  }
+  // price, percent, tick and kline distance between two overlay points
+  #measure({ tool, start }, end) {
+    const from = this.#canvas1.toChartPoint(start.x, start.y);
+    const to = this.#canvas1.toChartPoint(end.x, end.y);
+    const priceChange = to.price - from.price;
+    const ticks = Math.round(priceChange / this.#canvas1.bucketSize);
+    const bars = Math.round((to.time - from.time) / 60000);
+
+    return {
+      tool,
+      start,
+      end,
+      label: `${Number(priceChange.toFixed(4))} (${(
+        (priceChange / from.price) *
+        100
+      ).toFixed(2)}%) ${ticks} ticks, ${bars} bars`,
+    };
+  }
+  setTool(tool) {
+    this.activeTool = tool;
+    document.querySelectorAll(".js-tool-btn").forEach((button) => {
+      button.classList.toggle("active", button.dataset.tool === tool);
+    });
+    const overlayClasses = this.#canvas1_Overlay.canvas.classList;
+    overlayClasses.toggle("drawing", tool === "text");
+    overlayClasses.toggle("measuring", tool === "ruler" || tool === "fib");
+  }
+  #saveDrawings(key, drawings) {
+    localStorage.setItem(key, JSON.stringify(drawings));
+  }
+  #overlayPoint(event) {
+    const rect = this.#canvas1_Overlay.canvas.getBoundingClientRect();
+    return { x: event.clientX - rect.left, y: event.clientY - rect.top };
+  }
+  // shared by every trade fetch so parallel workers back off together, goes
+  // by the used weight header when it's readable cross-origin and by the
+  // weight this tab spent in the current minute otherwise
+  async waitForRateLimit(weight = AGG_TRADES_WEIGHT) {
+    this.#rollLocalWeight();
+    if (
+      this.#throttledUntil <= Date.now() &&
+      Math.max(this.#usedWeight, this.#localWeight) >=
+        BINANCE_WEIGHT_LIMIT * 0.9
+    ) {
+      // the weight counter resets at the start of each minute
+      this.#throttledUntil = Date.now() + 60000 - (Date.now() % 60000);
+      this.#usedWeight = 0;
+      this.updateThrottleInfo();
+    }
+    const waitTime = this.#throttledUntil - Date.now();
+    if (waitTime > 0) {
+      await new Promise((resolve) => setTimeout(resolve, waitTime));
+    }
+    this.#rollLocalWeight();
+    this.#localWeight += weight;
+  }
+  #rollLocalWeight() {
+    const minute = Math.floor(Date.now() / 60000);
+    if (minute !== this.#localWeightMinute) {
+      this.#localWeightMinute = minute;
+      this.#localWeight = 0;
+    }
+  }
+  updateThrottleInfo(active = true) {
+    const throttleInfo = document.querySelector("#throttle-info");
+    const waitTime = this.#throttledUntil - Date.now();
+
+    if (!active) {
+      throttleInfo.textContent = "";
+    } else if (waitTime > 0) {
+      throttleInfo.textContent = `rate limited, paused ${Math.ceil(
+        waitTime / 1000
+      )}s`;
+    } else if (this.#usedWeight) {
+      throttleInfo.textContent = `req. weight ${this.#usedWeight}/${BINANCE_WEIGHT_LIMIT}`;
+    }
+  }
   updateScaleBtn() {
     if (this.#autoScale) {
       this.#canvas1.resetZoomAndPan();
@@ -357,13 +932,29 @@ export class CanvasController {
     }
   }
   updateData(data) {
+    const isNewKline = this.#kline && data.kline.k.t !== this.#kline.k.t;
     this.#kline = data.kline;
     this.#depth = data.depth;
 
+    // keep the viewport on the same klines instead of scrolling with the new one
+    if (
+      isNewKline &&
+      (this.followMode === "never" ||
+        (this.followMode === "edge" && !this.#canvas1.isAtLiveEdge()))
+    ) {
+      const dx = this.#canvas1.minuteWidth;
+      this.#canvas1.panX(dx);
+      this.#canvas3.panX(dx);
+      this.#canvas4.panX(dx);
+    }
+
+    this.checkLargeTrades(data.tradesBuffer);
+
+    this.#canvas1.referencePrices = data.markPrice;
     this.#canvas1.updateData(data.kline, data.tradesBuffer);
     this.#canvas2.updateData(data.kline, data.depth);
     this.#canvas3.updateData(data.kline);
-    this.#canvas4.updateData(data.kline, data.tradesBuffer);
+    this.#canvas4.updateData(data.kline, data.tradesBuffer, data.depth);
   }
   startNew(symbol, tickSize, minQty, initialPrice) {
     this.#canvasStarted = false;
@@ -383,6 +974,11 @@ export class CanvasController {
     this.#canvas3.resetData();
     this.#canvas4.resetData();
 
+    this.#annotations[symbol] ??= [];
+    this.#canvas1.annotations = this.#annotations[symbol];
+    this.#fibs[symbol] ??= [];
+    this.#canvas1.fibs = this.#fibs[symbol];
+
     if (!this.#canvasStarted) {
       setTimeout(() => {
         this.#canvasStarted = true;
@@ -422,6 +1018,28 @@ class Canvas1 {
   #gotHistTrades = false;
   #gettingHistTrades = false;
   #scaleFactor;
+  annotations = [];
+  #annotationBoxes = [];
+  fibs = [];
+  #fibBoxes = [];
+  #marker = null;
+  referencePrices = null;
+  referenceLines = { mark: false, index: false };
+  highlightTopN = 0;
+  highlightPercentile = 0;
+  #percentileQty = Infinity;
+  #visibleCellSizes = [];
+  breakEvenBps = 0;
+  lastPriceLine = { style: "off", width: 1, color: "#c8c8c8" };
+  markLiveKline = true;
+  valueAreaPct = 0;
+  hideWicksBelow = 20;
+  minStudyTrades = 0;
+  deltaBodyColor = false;
+  showTradeCounts = false;
+  showPocLine = false;
+  pocColor = "#eed88b";
+  #pocPoints = [];
   constructor(controller, ctx, canvas, width, height) {
     this.#controller = controller;
     this.#ctx = ctx;
@@ -431,6 +1049,15 @@ class Canvas1 {
     this.#minuteWidth = Math.round((1 / this.#xZoom) * this.#width);
   }
 
+  get minuteWidth() {
+    return this.#minuteWidth;
+  }
+  isAtLiveEdge() {
+    return this.#panXoffset <= this.#minuteWidth;
+  }
+  panX(dx) {
+    this.#panXoffset = this.#panXoffset + dx < 0 ? 0 : this.#panXoffset + dx;
+  }
   panXY(dx, dy) {
     this.#autoScale = false;
 
@@ -519,6 +1146,7 @@ class Canvas1 {
     this.#gettingHistTrades = true;
 
     // get current kline first
+    const batchSize = this.#controller.histTradesBatchSize;
     let startTime = this.#currentDataPoint.startTime;
     const endTime = Date.now();
     let trades = [];
@@ -526,11 +1154,12 @@ class Canvas1 {
     console.log("getting current trades...");
     do {
       try {
+        await this.#controller.waitForRateLimit();
         const fetchedTrades = await this.#controller.fetchHistTrades(
           symbol,
           startTime,
           endTime,
-          1000
+          batchSize
         );
         trades = trades.concat(fetchedTrades);
         lastTradeTime = fetchedTrades[fetchedTrades.length - 1].x;
@@ -543,51 +1172,68 @@ class Canvas1 {
     } while (lastTradeTime < endTime);
     this.#currentKlineTrades = trades;
 
-    // get historical klines after
-    for (let i = 0; i < this.#dataPoints.length; i++) {
-      const kline = this.#dataPoints[i];
-      let startTime = kline.startTime;
-      const endTime = kline.endTime;
-      let trades = [];
-      let lastTradeTime = 0;
-      console.log(
-        "getting historical trades:",
-        i + 1,
-        "of",
-        this.#dataPoints.length,
-        "klines..."
-      );
-      while (true) {
-        if (symbol != currentSymbol) {
-          console.log("stopped fetching historical trades for", symbol);
-          this.#gettingHistTrades = false;
-          return;
-        }
-        try {
-          const fetchedTrades = await this.#controller.fetchHistTrades(
-            currentSymbol,
-            startTime,
-            endTime,
-            1000
-          );
-          trades = trades.concat(fetchedTrades);
-          if (fetchedTrades.length > 0) {
-            lastTradeTime = fetchedTrades[fetchedTrades.length - 1].x;
-            startTime = lastTradeTime + 1;
+    // get historical klines after, each worker takes the next kline and
+    // stores its trades at the kline's own index
+    let nextIndex = 0;
+    const fetchKlinesTrades = async () => {
+      while (nextIndex < this.#dataPoints.length) {
+        const i = nextIndex++;
+        const kline = this.#dataPoints[i];
+        let startTime = kline.startTime;
+        const endTime = kline.endTime;
+        let trades = [];
+        let lastTradeTime = 0;
+        console.log(
+          "getting historical trades:",
+          i + 1,
+          "of",
+          this.#dataPoints.length,
+          "klines..."
+        );
+        while (true) {
+          if (symbol != currentSymbol) {
+            return;
           }
-          if (fetchedTrades.length < 1000) {
+          try {
+            await this.#controller.waitForRateLimit();
+            const fetchedTrades = await this.#controller.fetchHistTrades(
+              currentSymbol,
+              startTime,
+              endTime,
+              batchSize
+            );
+            trades = trades.concat(fetchedTrades);
+            if (fetchedTrades.length > 0) {
+              lastTradeTime = fetchedTrades[fetchedTrades.length - 1].x;
+              startTime = lastTradeTime + 1;
+            }
+            if (fetchedTrades.length < batchSize) {
+              break;
+            }
+            await new Promise((resolve) => setTimeout(resolve, 400));
+          } catch (error) {
+            console.log(error, startTime, endTime);
             break;
           }
-          await new Promise((resolve) => setTimeout(resolve, 400));
-        } catch (error) {
-          console.log(error, startTime, endTime);
-          break;
         }
+        this.#klinesTrades[i] = trades;
       }
-      this.#klinesTrades[i] = trades;
+    };
+    await Promise.all(
+      Array.from(
+        { length: this.#controller.histTradesConcurrency },
+        fetchKlinesTrades
+      )
+    );
+    if (symbol != currentSymbol) {
+      console.log("stopped fetching historical trades for", symbol);
+      this.#gettingHistTrades = false;
+      this.#controller.updateThrottleInfo(false);
+      return;
     }
     this.#gettingHistTrades = false;
     this.#gotHistTrades = true;
+    this.#controller.updateThrottleInfo(false);
   }
   updateData(kline, aggTrades) {
     const {
@@ -611,6 +1257,13 @@ class Canvas1 {
         this.#klinesTrades.push(this.#currentKlineTrades);
 
         this.#currentKlineTrades = [];
+        // trades backfill writes by index, so only drop klines once it's done
+        const excess =
+          this.#dataPoints.length - this.#controller.klineRetention;
+        if (excess > 0 && !this.#gettingHistTrades) {
+          this.#dataPoints.splice(0, excess);
+          this.#klinesTrades.splice(0, excess);
+        }
       }
       this.#lastKlineEnd = endTime;
     }
@@ -680,11 +1333,16 @@ class Canvas1 {
     const rightX = this.#width - this.#panXoffset;
 
     let maxQtyInViewport = 0;
+    let anchorPrice = null;
+    this.#pocPoints = [];
+    this.#visibleCellSizes = [];
     this.#dataPoints.forEach((data, index) => {
       const x = Math.round(
         ((data.startTime - timeDifference) / zoomScale) * this.#width
       );
       if (x >= leftX && x <= rightX) {
+        if (anchorPrice === null) anchorPrice = Number(data.openPrice);
+
         const maxQtyDataPoint = this.drawDataPoint(
           this.#klinesTrades[index],
           data,
@@ -698,36 +1356,395 @@ class Canvas1 {
       const maxQtyCurrentKline = this.drawDataPoint(
         this.#currentKlineTrades,
         this.#currentDataPoint,
-        currentKlineX + this.#panXoffset
+        currentKlineX + this.#panXoffset,
+        this.markLiveKline
       );
       maxQtyInViewport = Math.max(maxQtyInViewport, maxQtyCurrentKline);
     }
     this.maxQty = maxQtyInViewport;
+    // like maxQty, the percentile applies from the next frame
+    this.#percentileQty = this.percentileOf(
+      this.#visibleCellSizes,
+      this.highlightPercentile
+    );
+    this.drawPocLine();
+    this.drawFibs(timeDifference, zoomScale);
+    this.drawAnnotations(timeDifference, zoomScale);
+    this.drawMarker(timeDifference, zoomScale);
+    this.drawReferenceLines();
+    this.drawLastPriceLine();
+    this.drawBreakEvenLines();
+    // percentage scale is measured from the open of the leftmost visible kline
+    this.#controller.anchorPrice =
+      anchorPrice ?? Number(this.#currentDataPoint.openPrice);
+  }
+  // read only, lists kline gaps and trades out of order or outside their kline
+  integrityReport() {
+    if (!this.#currentDataPoint) return [];
+
+    const findings = [];
+    const klines = [...this.#dataPoints, this.#currentDataPoint];
+    const klinesTrades = [...this.#klinesTrades, this.#currentKlineTrades];
+    const time = (ms) => new Date(ms).toLocaleTimeString();
+    klines.forEach((kline, index) => {
+      const previous = klines[index - 1];
+      if (previous && kline.startTime - previous.startTime !== 60000) {
+        findings.push(
+          `kline ${time(kline.startTime)} follows ${time(
+            previous.startTime
+          )}, expected 1m apart`
+        );
+      }
+      const trades = [].concat(...(klinesTrades[index] ?? []));
+      const unordered = trades.filter(
+        (trade, i) => i > 0 && trade.x < trades[i - 1].x
+      ).length;
+      const outside = trades.filter(
+        (trade) => trade.x < kline.startTime || trade.x > kline.endTime
+      ).length;
+      if (unordered > 0) {
+        findings.push(
+          `kline ${time(kline.startTime)} has ${unordered} trades out of order`
+        );
+      }
+      if (outside > 0) {
+        findings.push(
+          `kline ${time(kline.startTime)} holds ${outside} trades outside it`
+        );
+      }
+    });
+    return findings;
+  }
+  findExtreme(kind) {
+    if (!this.#currentDataPoint) return null;
+
+    return [...this.#dataPoints, this.#currentDataPoint].reduce(
+      (extreme, kline) => {
+        const price = Number(
+          kind === "high" ? kline.highPrice : kline.lowPrice
+        );
+        const isBeyond =
+          !extreme ||
+          (kind === "high" ? price > extreme.price : price < extreme.price);
+        return isBeyond ? { time: kline.startTime, price } : extreme;
+      },
+      null
+    );
+  }
+  // pan offsets (in pixels) that bring a time/price point to the center of the chart
+  offsetToCenter({ time, price }) {
+    const zoomScale = this.#xZoom * 60 * 1000;
+    const timeDifference = this.#currentDataPoint.startTime + 60000 - zoomScale;
+    const x =
+      ((time - timeDifference) / zoomScale) * this.#width + this.#panXoffset;
+
+    return {
+      dx: this.#width / 2 - this.#minuteWidth / 2 - x,
+      dy: (price - (this.#yMin + this.#yMax) / 2) * this.#scaleFactor,
+    };
+  }
+  flashMarker({ time, price }) {
+    this.#marker = { time, price, until: Date.now() + 2000 };
+  }
+  drawMarker(timeDifference, zoomScale) {
+    if (!this.#marker || Date.now() > this.#marker.until) {
+      this.#marker = null;
+      return;
+    }
+    const x =
+      Math.round(
+        ((this.#marker.time - timeDifference) / zoomScale) * this.#width
+      ) +
+      this.#panXoffset +
+      this.#minuteWidth / 2;
+    const y = Math.round(
+      this.#height - (this.#marker.price - this.#yMin) * this.#scaleFactor
+    );
+
+    this.#ctx.beginPath();
+    this.#ctx.arc(x, y, this.#minuteWidth / 3, 0, 2 * Math.PI);
+    this.#ctx.strokeStyle = "rgba(238, 216, 139, 0.9)";
+    this.#ctx.lineWidth = 2;
+    this.#ctx.stroke();
+    this.#ctx.lineWidth = 1;
+  }
+  drawReferenceLines() {
+    if (!this.referencePrices) return;
+
+    const colors = {
+      mark: "rgba(238, 216, 139, 0.7)",
+      index: "rgba(139, 180, 238, 0.7)",
+    };
+    Object.entries(colors).forEach(([line, color]) => {
+      if (!this.referenceLines[line]) return;
+
+      const price = this.referencePrices[line];
+      const y = Math.round(
+        this.#height - (price - this.#yMin) * this.#scaleFactor
+      );
+      this.#ctx.beginPath();
+      this.#ctx.setLineDash([4, 4]);
+      this.#ctx.moveTo(0, y);
+      this.#ctx.lineTo(this.#width, y);
+      this.#ctx.strokeStyle = color;
+      this.#ctx.stroke();
+      this.#ctx.setLineDash([]);
+
+      this.#ctx.font = this.#controller.labelFont(-1);
+      this.#ctx.fillStyle = color;
+      const text = `${line} ${price}`;
+      const textWidth = this.#ctx.measureText(text).width;
+      this.#ctx.fillText(text, this.#width - textWidth - 5, y - 3);
+    });
+  }
+  // joins consecutive klines' POCs, klines without trades break the line
+  drawPocLine() {
+    if (!this.showPocLine) return;
+
+    this.#ctx.beginPath();
+    let drawing = false;
+    this.#pocPoints.forEach((point) => {
+      if (!point) {
+        drawing = false;
+        return;
+      }
+      const y = Math.round(
+        this.#height - (point.price - this.#yMin) * this.#scaleFactor
+      );
+      if (drawing) {
+        this.#ctx.lineTo(point.x, y);
+      } else {
+        this.#ctx.moveTo(point.x, y);
+        drawing = true;
+      }
+    });
+    this.#ctx.strokeStyle = this.pocColor;
+    this.#ctx.stroke();
+  }
+  // the price tag on the orderbook side stays, this extends it over the klines
+  drawLastPriceLine() {
+    const { style, width, color } = this.lastPriceLine;
+    if (style === "off") return;
+
+    const dashes = { solid: [], dashed: [6, 4], dotted: [1, 3] };
+    const y = Math.round(
+      this.#height -
+        (this.#currentDataPoint.closePrice - this.#yMin) * this.#scaleFactor
+    );
+    this.#ctx.beginPath();
+    this.#ctx.setLineDash(dashes[style]);
+    this.#ctx.moveTo(0, y);
+    this.#ctx.lineTo(this.#width, y);
+    this.#ctx.lineWidth = width;
+    this.#ctx.strokeStyle = color;
+    this.#ctx.stroke();
+    this.#ctx.setLineDash([]);
+    this.#ctx.lineWidth = 1;
   }
-  drawDataPoint(trades, kline, x) {
+  // fee adjusted levels around the last price, a display aid only
+  drawBreakEvenLines() {
+    if (!this.breakEvenBps) return;
+
+    const lastPrice = Number(this.#currentDataPoint.closePrice);
+    [1, -1].forEach((sign) => {
+      const price = lastPrice * (1 + (sign * this.breakEvenBps) / 10000);
+      const y = Math.round(
+        this.#height - (price - this.#yMin) * this.#scaleFactor
+      );
+      this.#ctx.beginPath();
+      this.#ctx.setLineDash([2, 4]);
+      this.#ctx.moveTo(0, y);
+      this.#ctx.lineTo(this.#width, y);
+      this.#ctx.strokeStyle = "rgba(200, 200, 200, 0.5)";
+      this.#ctx.stroke();
+      this.#ctx.setLineDash([]);
+
+      this.#ctx.font = this.#controller.labelFont(-1);
+      this.#ctx.fillStyle = "rgba(200, 200, 200, 0.7)";
+      const text = `${sign > 0 ? "+" : "-"}${this.breakEvenBps}bps ${Number(
+        price.toFixed(4)
+      )}`;
+      this.#ctx.fillText(text, 5, y - 3);
+    });
+  }
+  toChartPoint(x, y) {
+    const zoomScale = this.#xZoom * 60 * 1000;
+    const timeDifference = this.#currentDataPoint.startTime + 60000 - zoomScale;
+    return {
+      time: ((x - this.#panXoffset) / this.#width) * zoomScale + timeDifference,
+      price: this.#yMin + (this.#height - y) / this.#scaleFactor,
+    };
+  }
+  annotationAt(x, y) {
+    const box = this.#annotationBoxes.find(
+      (box) =>
+        x >= box.x &&
+        x <= box.x + box.width &&
+        y >= box.y &&
+        y <= box.y + box.height
+    );
+    return box ? box.annotation : null;
+  }
+  fibAt(x, y) {
+    const box = this.#fibBoxes.find(
+      (box) => x >= box.x && box.ys.some((levelY) => Math.abs(levelY - y) <= 4)
+    );
+    return box ? box.fib : null;
+  }
+  fibAnchorAt(x, y) {
+    for (const { fib, anchors } of this.#fibBoxes) {
+      const anchor = anchors.find(
+        (anchor) => Math.hypot(anchor.x - x, anchor.y - y) <= 6
+      );
+      if (anchor) return { fib, end: anchor.end };
+    }
+    return null;
+  }
+  // levels run from the drag's end (0) back to its start (1), out to the
+  // right edge from the earlier anchor, both anchors can be dragged
+  drawFibs(timeDifference, zoomScale) {
+    this.#fibBoxes = [];
+
+    this.fibs.forEach((fib) => {
+      const { from, to } = fib;
+      const toX = (time) =>
+        Math.round(((time - timeDifference) / zoomScale) * this.#width) +
+        this.#panXoffset;
+      const toY = (price) =>
+        Math.round(this.#height - (price - this.#yMin) * this.#scaleFactor);
+      const x = toX(Math.min(from.time, to.time));
+      const ys = [];
+      const anchors = [
+        { end: "from", x: toX(from.time), y: toY(from.price) },
+        { end: "to", x: toX(to.time), y: toY(to.price) },
+      ];
+
+      this.#ctx.font = this.#controller.labelFont(-1);
+      this.#controller.fibLevels.forEach((ratio) => {
+        const price = to.price + (from.price - to.price) * ratio;
+        const y = toY(price);
+        ys.push(y);
+
+        this.#ctx.beginPath();
+        this.#ctx.moveTo(x, y);
+        this.#ctx.lineTo(this.#width, y);
+        this.#ctx.strokeStyle = "rgba(139, 180, 238, 0.5)";
+        this.#ctx.stroke();
+
+        this.#ctx.fillStyle = "rgba(139, 180, 238, 0.8)";
+        this.#ctx.fillText(`${ratio} (${Number(price.toFixed(4))})`, x, y - 3);
+      });
+      anchors.forEach((anchor) => {
+        this.#ctx.beginPath();
+        this.#ctx.arc(anchor.x, anchor.y, 3, 0, 2 * Math.PI);
+        this.#ctx.fillStyle = "rgba(139, 180, 238, 0.8)";
+        this.#ctx.fill();
+      });
+      this.#fibBoxes.push({ fib, x, ys, anchors });
+    });
+  }
+  drawAnnotations(timeDifference, zoomScale) {
+    this.#annotationBoxes = [];
+
+    this.annotations.forEach((annotation) => {
+      const { text, fontSize } = annotation;
+      const x =
+        Math.round(
+          ((annotation.time - timeDifference) / zoomScale) * this.#width
+        ) + this.#panXoffset;
+      const y = Math.round(
+        this.#height - (annotation.price - this.#yMin) * this.#scaleFactor
+      );
+
+      this.#ctx.font = `${fontSize}px monospace`;
+      const box = {
+        annotation,
+        x: x - 3,
+        y: y - fontSize,
+        width: this.#ctx.measureText(text).width + 6,
+        height: fontSize + 4,
+      };
+      this.#ctx.fillStyle = "rgba(33, 33, 33, 0.8)";
+      this.#ctx.fillRect(box.x, box.y, box.width, box.height);
+      this.#ctx.fillStyle = "#c8c8c8";
+      this.#ctx.fillText(text, x, y);
+
+      this.#annotationBoxes.push(box);
+    });
+  }
+  drawDataPoint(trades, kline, x, provisional = false) {
     let maxQtyKline = 0;
+    let pocPrice = null;
+    let bodyColor = null;
     if (trades) {
       const flatTrades = [].concat(...trades);
+      if (this.deltaBodyColor && flatTrades.length > 0) {
+        bodyColor = this.deltaColor(flatTrades);
+      }
       const groupedTrades = flatTrades.reduce((acc, aggTrade) => {
-        const roundedY =
-          Math.round(aggTrade.y / this.bucketSize) * this.bucketSize;
+        const roundedY = roundToBucket(
+          aggTrade.y,
+          this.bucketSize,
+          this.#controller.roundingMode
+        );
         const key = `${roundedY}-${aggTrade.m}`;
         if (!acc[key]) {
-          acc[key] = { ...aggTrade, y: roundedY, q: 0 };
+          acc[key] = { ...aggTrade, y: roundedY, q: 0, count: 0 };
         }
         acc[key].q += aggTrade.q;
+        acc[key].count++;
         return acc;
       }, {});
       maxQtyKline = Math.max(
         ...Object.values(groupedTrades).map((trade) => trade.q)
       );
+      // thin klines get no studies, a handful of trades makes them noise
+      const studiesEnabled = flatTrades.length >= this.minStudyTrades;
+      // smallest size that still ranks within the kline's top N levels
+      const highlightQty =
+        studiesEnabled && this.highlightTopN > 0
+          ? Object.values(groupedTrades)
+              .map((trade) => trade.q)
+              .sort((a, b) => b - a)
+              .slice(0, this.highlightTopN)
+              .pop()
+          : Infinity;
+      if (studiesEnabled && this.highlightPercentile > 0) {
+        Object.values(groupedTrades).forEach((trade) =>
+          this.#visibleCellSizes.push(trade.q)
+        );
+      }
+      if (studiesEnabled && (this.valueAreaPct > 0 || this.showPocLine)) {
+        const profile = this.volumeProfile(groupedTrades);
+        if (this.valueAreaPct > 0) {
+          this.drawValueArea(x, profile);
+        }
+        const { levels, volumes } = profile;
+        pocPrice = levels[volumes.indexOf(Math.max(...volumes))] ?? null;
+      }
       if (this.maxQty !== 0) {
         Object.values(groupedTrades).forEach((aggTrade) => {
           const yTradePrice = Math.round(
             this.#height - (aggTrade.y - this.#yMin) * this.#scaleFactor
           );
           const quantityScaled = this.scaleQuantity(aggTrade.q);
-          this.drawTradesAt(x, yTradePrice, aggTrade.m, quantityScaled);
+          this.drawTradesAt(
+            x,
+            yTradePrice,
+            aggTrade.m,
+            quantityScaled,
+            aggTrade.q >= highlightQty ||
+              (studiesEnabled && aggTrade.q > this.#percentileQty)
+          );
+          if (this.showTradeCounts) {
+            this.drawTradeCount(
+              x,
+              yTradePrice,
+              aggTrade.m,
+              quantityScaled,
+              aggTrade.count
+            );
+          }
         });
       }
     }
@@ -744,24 +1761,109 @@ class Canvas1 {
       this.#height - (kline.closePrice - this.#yMin) * this.#scaleFactor
     );
 
-    this.drawKlineAt(x, yHigh - 2);
-    this.drawKlineAt(x, yLow + 2);
+    // high and low ticks turn to clutter on narrow klines
+    if (this.#minuteWidth >= this.hideWicksBelow) {
+      this.drawKlineAt(x, yHigh - 2);
+      this.drawKlineAt(x, yLow + 2);
+    }
 
     this.#ctx.beginPath();
     this.#ctx.moveTo(x + this.#minuteWidth / 2, yOpen);
     this.#ctx.lineTo(x + this.#minuteWidth / 2, yClose);
-    this.#ctx.shadowColor =
-      yClose < yOpen ? "rgba(155, 230, 209, 0.5)" : "rgba(230, 161, 160, 0.5)";
+    // delta coloring replaces the close vs open colors, glow included
+    const [shadowColor, strokeColor] = bodyColor
+      ? [bodyColor, bodyColor]
+      : yClose < yOpen
+      ? ["rgba(155, 230, 209, 0.5)", "rgba(155, 230, 209, 0.7)"]
+      : ["rgba(230, 161, 160, 0.5)", "rgba(230, 161, 160, 0.7)"];
+    this.#ctx.shadowColor = shadowColor;
     this.#ctx.shadowBlur = 5;
-    this.#ctx.strokeStyle =
-      yClose < yOpen ? "rgba(155, 230, 209, 0.7)" : "rgba(230, 161, 160, 0.7)";
+    this.#ctx.strokeStyle = strokeColor;
+    // the forming kline isn't final yet, draw its body dashed
+    if (provisional) this.#ctx.setLineDash([2, 2]);
     this.#ctx.stroke();
     //reset shadow
     this.#ctx.shadowColor = "transparent";
     this.#ctx.shadowBlur = 0;
+    this.#ctx.setLineDash([]);
+
+    if (this.showPocLine) {
+      this.#pocPoints.push(
+        pocPrice === null
+          ? null
+          : { x: x + this.#minuteWidth / 2, price: pocPrice }
+      );
+    }
 
     return maxQtyKline;
   }
+  // side of the kline's net delta, more opaque the more one sided it was
+  deltaColor(trades) {
+    let buyVolume = 0;
+    let sellVolume = 0;
+    trades.forEach((trade) => {
+      if (trade.m) {
+        sellVolume += trade.q;
+      } else {
+        buyVolume += trade.q;
+      }
+    });
+    const delta = buyVolume - sellVolume;
+    const imbalance = Math.abs(delta) / (buyVolume + sellVolume);
+    const { buy, sell } = this.#controller.colors;
+    return hexToRgba(delta >= 0 ? buy : sell, 0.3 + 0.7 * imbalance);
+  }
+  percentileOf(values, percentile) {
+    if (!percentile || values.length === 0) return Infinity;
+
+    const sorted = [...values].sort((a, b) => a - b);
+    return sorted[Math.floor(((sorted.length - 1) * percentile) / 100)];
+  }
+  // both sides' volume per price level, ordered by price
+  volumeProfile(groupedTrades) {
+    const volumeByPrice = {};
+    Object.values(groupedTrades).forEach((trade) => {
+      volumeByPrice[trade.y] = (volumeByPrice[trade.y] ?? 0) + trade.q;
+    });
+    const levels = Object.keys(volumeByPrice)
+      .map(Number)
+      .sort((a, b) => a - b);
+    return { levels, volumes: levels.map((price) => volumeByPrice[price]) };
+  }
+  // grows from the POC towards the heavier neighbouring level until the
+  // range holds valueAreaPct of the kline's volume
+  drawValueArea(x, { levels, volumes }) {
+    if (levels.length === 0) return;
+
+    const target =
+      (volumes.reduce((sum, q) => sum + q, 0) * this.valueAreaPct) / 100;
+
+    let low = volumes.indexOf(Math.max(...volumes));
+    let high = low;
+    let areaVolume = volumes[low];
+    while (areaVolume < target) {
+      const below = low > 0 ? volumes[low - 1] : -1;
+      const above = high < volumes.length - 1 ? volumes[high + 1] : -1;
+      if (above >= below) {
+        high++;
+        areaVolume += above;
+      } else {
+        low--;
+        areaVolume += below;
+      }
+    }
+    const halfBucket = this.bucketSize / 2;
+    const yTop = Math.round(
+      this.#height -
+        (levels[high] + halfBucket - this.#yMin) * this.#scaleFactor
+    );
+    const yBottom = Math.round(
+      this.#height -
+        (levels[low] - halfBucket - this.#yMin) * this.#scaleFactor
+    );
+    this.#ctx.fillStyle = "rgba(200, 200, 200, 0.06)";
+    this.#ctx.fillRect(x + 1, yTop, this.#minuteWidth - 2, yBottom - yTop);
+  }
   drawKlineAt(x, y) {
     this.#ctx.beginPath();
     this.#ctx.moveTo(x + 1, y);
@@ -769,18 +1871,47 @@ class Canvas1 {
     this.#ctx.strokeStyle = "rgba(200, 200, 200, 0.5)";
     this.#ctx.stroke();
   }
-  drawTradesAt(x, y, side, quantity) {
+  drawTradesAt(x, y, side, quantity, highlighted = false) {
     this.#ctx.beginPath();
     if (!side) {
       this.#ctx.moveTo(x + 4 + this.#minuteWidth / 2, y);
       this.#ctx.lineTo(x + 4 + this.#minuteWidth / 2 + quantity, y);
-      this.#ctx.strokeStyle = `rgba(81, 205, 160, 1)`;
+      this.#ctx.strokeStyle = this.#controller.colors.buy;
     } else {
       this.#ctx.moveTo(x - 4 + this.#minuteWidth / 2, y);
       this.#ctx.lineTo(x - 4 + this.#minuteWidth / 2 - quantity, y);
-      this.#ctx.strokeStyle = `rgba(192, 80, 77, 1)`;
+      this.#ctx.strokeStyle = this.#controller.colors.sell;
+    }
+    if (highlighted) {
+      this.#ctx.lineWidth = 3;
+      this.#ctx.shadowColor = this.#ctx.strokeStyle;
+      this.#ctx.shadowBlur = 6;
     }
     this.#ctx.stroke();
+    //reset highlight
+    this.#ctx.lineWidth = 1;
+    this.#ctx.shadowColor = "transparent";
+    this.#ctx.shadowBlur = 0;
+  }
+  // counts aggregated trades, each one can hold several fills at one price
+  drawTradeCount(x, y, side, quantity, count) {
+    if (this.#minuteWidth < 40) return;
+
+    this.#ctx.font = this.#controller.labelFont(-3);
+    this.#ctx.fillStyle = "rgba(200, 200, 200, 0.7)";
+    this.#ctx.textBaseline = "middle";
+    const text = String(count);
+    if (!side) {
+      this.#ctx.fillText(text, x + 6 + this.#minuteWidth / 2 + quantity, y);
+    } else {
+      const textWidth = this.#ctx.measureText(text).width;
+      this.#ctx.fillText(
+        text,
+        x - 6 + this.#minuteWidth / 2 - quantity - textWidth,
+        y
+      );
+    }
+    this.#ctx.textBaseline = "alphabetic";
   }
   scaleQuantity(quantity) {
     const minLineLength = 0;
@@ -805,6 +1936,13 @@ class Canvas2 {
   #minMultiplier = 0.997;
   #maxMultiplier = 1.003;
   bucketSize;
+  minOrderSize = 0;
+  showCountdown = false;
+  scaleMode = "frame";
+  maxSpreadPct = 0;
+  #wideSpread = null;
+  #rollingMax = 0;
+  #rollingMaxTime = 0;
   #autoScale = true;
   #panYoffset = 0;
   #maxQuantity;
@@ -847,20 +1985,39 @@ class Canvas2 {
   resetData() {
     this.#kline = null;
     this.#depth = null;
+    this.#wideSpread = null;
     this.#yMin = null;
     this.#yMax = null;
     this.#autoScale = true;
     this.#maxQuantity = Math.round(1000000 / this.#controller.initialPrice);
     this.#defaultMaxQty = this.#maxQuantity;
+    this.#rollingMax = 0;
   }
   updateData(kline, depth) {
     const {
-      k: { o: openPrice, h: highPrice, l: lowPrice, c: closePrice },
+      k: {
+        o: openPrice,
+        h: highPrice,
+        l: lowPrice,
+        c: closePrice,
+        T: endTime,
+      },
     } = kline;
-    this.#kline = { openPrice, highPrice, lowPrice, closePrice };
+    this.#kline = { openPrice, highPrice, lowPrice, closePrice, endTime };
 
     const { asks, bids } = depth;
-    this.#depth = { asks, bids };
+    // a sudden wide spread is usually a stale side, hold the last good book
+    const spreadPct =
+      asks?.length && bids?.length
+        ? ((asks[0][0] - bids[0][0]) / bids[0][0]) * 100
+        : 0;
+    this.#wideSpread =
+      this.maxSpreadPct > 0 && spreadPct > this.maxSpreadPct
+        ? spreadPct
+        : null;
+    if (this.#wideSpread === null || !this.#depth) {
+      this.#depth = { asks, bids };
+    }
 
     this.#yMin =
       Math.min(
@@ -894,8 +2051,11 @@ class Canvas2 {
     if (this.#depth.asks && this.#depth.bids) {
       const groupByBucketSize = (orders) => {
         return orders.reduce((grouped, order) => {
-          const bucket =
-            Math.round(order[0] / this.bucketSize) * this.bucketSize;
+          const bucket = roundToBucket(
+            order[0],
+            this.bucketSize,
+            this.#controller.roundingMode
+          );
           if (!grouped[bucket]) grouped[bucket] = 0;
           grouped[bucket] += parseFloat(order[1]);
           return grouped;
@@ -910,33 +2070,48 @@ class Canvas2 {
       ];
       this.maxQuantity = Math.max(this.#defaultMaxQty, ...quantities);
 
+      if (this.scaleMode === "rolling") {
+        const now = Date.now();
+        const decay = Math.pow(
+          0.5,
+          (now - this.#rollingMaxTime) / ROLLING_MAX_HALF_LIFE
+        );
+        this.#rollingMax = Math.max(this.maxQuantity, this.#rollingMax * decay);
+        this.#rollingMaxTime = now;
+        this.maxQuantity = this.#rollingMax;
+      }
+
+      // filtered levels still count towards maxQuantity
       Object.entries(groupedAsks).forEach(([price, quantity]) => {
+        if (price * quantity < this.minOrderSize) return;
         const y = Math.round(
           this.#height - (price - this.#yMin) * this.#scaleFactor
         );
-        this.drawLineAt(y, "#C0504E", quantity);
+        this.drawLineAt(y, this.#controller.colors.sell, quantity);
       });
       Object.entries(groupedBids).forEach(([price, quantity]) => {
+        if (price * quantity < this.minOrderSize) return;
         const y = Math.round(
           this.#height - (price - this.#yMin) * this.#scaleFactor
         );
-        this.drawLineAt(y, "#51CDA0", quantity);
+        this.drawLineAt(y, this.#controller.colors.buy, quantity);
       });
     }
-    this.#ctx.font = "10px monospace";
+    this.#ctx.font = this.#controller.labelFont(-1);
     this.#ctx.fillStyle = "#c8c8c8";
-    let text = Math.round(this.maxQuantity);
+    let text = formatQuantity(Math.round(this.maxQuantity));
     let textWidth = this.#ctx.measureText(text).width;
     this.#ctx.fillText(text, this.#width - 5 - textWidth, 20);
+    if (this.#wideSpread !== null) {
+      this.#ctx.fillStyle = this.#controller.colors.sell;
+      text = `held, spread ${this.#wideSpread.toFixed(2)}%`;
+      textWidth = this.#ctx.measureText(text).width;
+      this.#ctx.fillText(text, this.#width - 5 - textWidth, 34);
+    }
 
-    const colors = {
-      "#C0504E": "rgba(192, 80, 78, 0.5)",
-      "#51CDA0": "rgba(81, 205, 160, 0.5)",
-      "#c8c8c8": "rgba(200, 200, 200, 0.5)",
-    };
-    const color =
-      yClose > yOpen ? "#C0504E" : yClose < yOpen ? "#51CDA0" : "#c8c8c8";
-    const shadowColor = colors[color];
+    const { buy, sell } = this.#controller.colors;
+    const color = yClose > yOpen ? sell : yClose < yOpen ? buy : "#c8c8c8";
+    const shadowColor = hexToRgba(color, 0.5);
     this.drawTextWithBackground(
       yClose,
       Number(closePrice),
@@ -944,6 +2119,22 @@ class Canvas2 {
       color,
       shadowColor
     );
+    if (this.showCountdown) {
+      this.drawCountdown(yClose + 14, this.#kline.endTime);
+    }
+  }
+  drawCountdown(y, endTime) {
+    const remaining = Math.max(0, endTime + 1 - Date.now());
+    const minutes = Math.floor(remaining / 60000);
+    const seconds = Math.floor((remaining % 60000) / 1000);
+    const text =
+      minutes.toString().padStart(2, "0") +
+      ":" +
+      seconds.toString().padStart(2, "0");
+
+    this.#ctx.font = this.#controller.labelFont(-1);
+    this.#ctx.fillStyle = "#c8c8c8";
+    this.#ctx.fillText(text, 5, y);
   }
   drawLineAt(y, color, quantity) {
     const scaledQuantity = (quantity / this.maxQuantity) * (this.#width - 60);
@@ -956,7 +2147,7 @@ class Canvas2 {
     this.#ctx.stroke();
   }
   drawTextWithBackground(y, text, color, bg_color, shadowColor) {
-    this.#ctx.font = "11px monospace";
+    this.#ctx.font = this.#controller.labelFont();
     const textWidth = this.#ctx.measureText(text).width;
 
     this.#ctx.shadowColor = shadowColor;
@@ -984,6 +2175,8 @@ class Canvas3 {
   #panXoffset = 0;
   #gotHistKlines = false;
   #scaleFactor;
+  volumeMode = "split";
+  markZeroVolume = false;
   constructor(controller, ctx, canvas, width, height) {
     this.#controller = controller;
     this.#ctx = ctx;
@@ -1042,6 +2235,10 @@ class Canvas3 {
     if (this.#lastStartTime !== startTime) {
       if (this.#currentDataPoint) {
         this.#dataPoints.push(this.#currentDataPoint);
+
+        const excess =
+          this.#dataPoints.length - this.#controller.klineRetention;
+        if (excess > 0) this.#dataPoints.splice(0, excess);
       }
       this.#lastStartTime = startTime;
     }
@@ -1065,7 +2262,10 @@ class Canvas3 {
       return x >= leftX && x <= rightX;
     });
     this.#yMax = visibleDataPoints.reduce(
-      (max, data) => Math.max(max, data.buyVolume, data.sellVolume),
+      (max, data) =>
+        this.volumeMode === "stacked"
+          ? Math.max(max, Number(data.buyVolume) + Number(data.sellVolume))
+          : Math.max(max, data.buyVolume, data.sellVolume),
       0
     );
 
@@ -1105,25 +2305,73 @@ class Canvas3 {
 
     this.drawTimeLabel(x, kline.startTime);
 
+    // a kline that traded nothing, as opposed to one that is missing
+    if (
+      this.markZeroVolume &&
+      Number(kline.buyVolume) + Number(kline.sellVolume) === 0
+    ) {
+      this.#ctx.beginPath();
+      this.#ctx.moveTo(x + this.#minuteWidth / 4, this.#height - 21);
+      this.#ctx.lineTo(x + (this.#minuteWidth * 3) / 4, this.#height - 21);
+      this.#ctx.strokeStyle = "rgba(200, 200, 200, 0.3)";
+      this.#ctx.lineWidth = 2;
+      this.#ctx.stroke();
+      return;
+    }
+    if (this.volumeMode === "stacked") {
+      const yTotalVolume = Math.max(
+        0,
+        Math.round(
+          this.#height -
+            20 -
+            (Number(kline.buyVolume) + Number(kline.sellVolume)) *
+              this.#scaleFactor
+        )
+      );
+      this.drawKlineAt(
+        x + this.#minuteWidth / 2,
+        yBuyVolume,
+        this.#controller.colors.buy,
+        hexToRgba(this.#controller.colors.buy, 0.4),
+        this.#height - 20,
+        this.#minuteWidth / 3
+      );
+      this.drawKlineAt(
+        x + this.#minuteWidth / 2,
+        yTotalVolume,
+        this.#controller.colors.sell,
+        hexToRgba(this.#controller.colors.sell, 0.4),
+        yBuyVolume,
+        this.#minuteWidth / 3
+      );
+      return;
+    }
     this.drawKlineAt(
       x + this.#minuteWidth / 2 + this.#minuteWidth / 8,
       yBuyVolume,
-      "#51CDA0",
-      "rgba(81, 205, 160, 0.4)"
+      this.#controller.colors.buy,
+      hexToRgba(this.#controller.colors.buy, 0.4)
     );
     this.drawKlineAt(
       x + this.#minuteWidth / 2 - this.#minuteWidth / 8,
       ySellVolume,
-      "#C0504E",
-      "rgba(192, 80, 77, 0.4)"
+      this.#controller.colors.sell,
+      hexToRgba(this.#controller.colors.sell, 0.4)
     );
   }
-  drawKlineAt(x, y, color, shadowColor) {
+  drawKlineAt(
+    x,
+    y,
+    color,
+    shadowColor,
+    y0 = this.#height - 20,
+    lineWidth = this.#minuteWidth / 6
+  ) {
     this.#ctx.beginPath();
-    this.#ctx.moveTo(x, this.#height - 20);
+    this.#ctx.moveTo(x, y0);
     this.#ctx.lineTo(x, y);
     this.#ctx.strokeStyle = color;
-    this.#ctx.lineWidth = this.#minuteWidth / 6;
+    this.#ctx.lineWidth = lineWidth;
     this.#ctx.shadowColor = shadowColor;
     this.#ctx.shadowBlur = 3;
     this.#ctx.stroke();
@@ -1139,7 +2387,7 @@ class Canvas3 {
       ":" +
       date.getMinutes().toString().padStart(2, "0");
 
-    this.#ctx.font = "11px monospace";
+    this.#ctx.font = this.#controller.labelFont();
     this.#ctx.fillStyle = "#c8c8c8";
     this.#ctx.fillText(time, x, this.#height - 5);
   }
@@ -1164,6 +2412,14 @@ class Canvas4 {
   #cumVolumeDelta = 0;
   #oiEnabled = true;
   #cvdEnabled = true;
+  #lsEnabled = false;
+  #lsRatios = [];
+  #lsFetchedAt = 0;
+  #spreadEnabled = false;
+  #spreadSum = 0;
+  #spreadCount = 0;
+  oiAlert = { threshold: 0, window: 5 };
+  #oiAlertArmed = true;
   #scaleFactor_OI;
   #scaleFactor_CVD;
   constructor(controller, ctx, canvas, width, height) {
@@ -1200,15 +2456,24 @@ class Canvas4 {
     this.#yMin_CVD = null;
     this.#cumVolumeDelta = 0;
     this.#panXoffset = 0;
+    this.#oiAlertArmed = true;
+    this.#lsRatios = [];
+    this.#lsFetchedAt = 0;
+    this.#spreadSum = 0;
+    this.#spreadCount = 0;
   }
   toggleIndicator(indicator, state) {
     if (indicator === "oi") {
       this.#oiEnabled = state;
     } else if (indicator === "cvd") {
       this.#cvdEnabled = state;
+    } else if (indicator === "ls") {
+      this.#lsEnabled = state;
+    } else if (indicator === "spread") {
+      this.#spreadEnabled = state;
     }
   }
-  async updateData(kline, trades) {
+  async updateData(kline, trades, depth) {
     const {
       k: { t: startTime, T: endTime },
     } = kline;
@@ -1219,8 +2484,11 @@ class Canvas4 {
 
     if (this.#lastStartTime !== startTime) {
       if (this.#currentDataPoint) {
-        this.fetchOI(currentSymbol).then((OIValue) => {
+        const symbol = currentSymbol;
+        this.fetchOI(symbol).then((OIValue) => {
+          if (symbol !== currentSymbol) return;
           this.#OIDataPoints.push(OIValue);
+          this.checkOIAlert();
         });
         this.#dataPoints.push(this.#currentDataPoint);
 
@@ -1230,14 +2498,36 @@ class Canvas4 {
         }
       }
       this.#lastStartTime = startTime;
+      this.#spreadSum = 0;
+      this.#spreadCount = 0;
+    }
+    // mean spread of the depth updates seen in this kline, in bps
+    const { asks, bids } = depth ?? {};
+    if (asks?.length && bids?.length) {
+      this.#spreadSum += ((asks[0][0] - bids[0][0]) / bids[0][0]) * 10000;
+      this.#spreadCount++;
+    }
+    // samples come in 5m periods, checking once a minute is plenty
+    if (this.#lsEnabled && Date.now() - this.#lsFetchedAt > 60000) {
+      this.#lsFetchedAt = Date.now();
+      const symbol = currentSymbol;
+      this.fetchLongShortRatio(symbol).then((ratios) => {
+        if (ratios && symbol === currentSymbol) this.#lsRatios = ratios;
+      });
     }
     this.#currentDataPoint = {
       startTime,
       endTime,
       cumVolumeDelta: this.#cumVolumeDelta,
+      spread: this.#spreadCount ? this.#spreadSum / this.#spreadCount : null,
     };
 
-    if (this.#oiEnabled || this.#cvdEnabled) {
+    if (
+      this.#oiEnabled ||
+      this.#cvdEnabled ||
+      this.#lsEnabled ||
+      this.#spreadEnabled
+    ) {
       this.#yMax_OI =
         this.#OIDataPoints.length > 0
           ? Math.max(...this.#OIDataPoints.map(Number)) * 1.001
@@ -1301,6 +2591,12 @@ class Canvas4 {
       }
     });
 
+    if (this.#lsEnabled) {
+      this.drawLongShortRatio(timeDifference, zoomScale);
+    }
+    if (this.#spreadEnabled) {
+      this.drawSpread(timeDifference, zoomScale);
+    }
     if (this.#cvdEnabled) {
       const x = this.#width - this.#minuteWidth + this.#panXoffset;
       if (x >= leftX && x <= rightX) {
@@ -1321,6 +2617,105 @@ class Canvas4 {
       }
     }
   }
+  // edge triggered, re-arms once the change falls back under the threshold
+  checkOIAlert() {
+    const { threshold, window } = this.oiAlert;
+    if (!threshold || this.#OIDataPoints.length <= window) return;
+
+    const latest = Number(this.#OIDataPoints.at(-1));
+    const previous = Number(this.#OIDataPoints.at(-1 - window));
+    const change = ((latest - previous) / previous) * 100;
+
+    if (Math.abs(change) < threshold) {
+      this.#oiAlertArmed = true;
+    } else if (this.#oiAlertArmed) {
+      this.#oiAlertArmed = false;
+      this.#controller.notify(
+        `${currentSymbol} open interest ${change > 0 ? "up" : "down"} ${Math.abs(
+          change
+        ).toFixed(2)}% in ${window}m`,
+        "warn"
+      );
+    }
+  }
+  // a step line on its own scale, each sample holds until the next one
+  drawLongShortRatio(timeDifference, zoomScale) {
+    if (this.#lsRatios.length === 0) return;
+
+    const ratios = this.#lsRatios.map(({ ratio }) => ratio);
+    const yMax = Math.max(...ratios) * 1.01;
+    const yMin = Math.min(...ratios) * 0.99;
+    const scaleFactor = this.#height / (yMax - yMin);
+    const toY = (ratio) => this.#height - (ratio - yMin) * scaleFactor;
+
+    if (yMin < 1 && yMax > 1) {
+      this.#ctx.beginPath();
+      this.#ctx.setLineDash([2, 4]);
+      this.#ctx.moveTo(0, toY(1));
+      this.#ctx.lineTo(this.#width, toY(1));
+      this.#ctx.lineWidth = 1;
+      this.#ctx.strokeStyle = "rgba(200, 200, 200, 0.3)";
+      this.#ctx.stroke();
+      this.#ctx.setLineDash([]);
+    }
+    this.#lsRatios.forEach(({ time, ratio }, index) => {
+      const x =
+        ((time - timeDifference) / zoomScale) * this.#width + this.#panXoffset;
+      const next = this.#lsRatios[index + 1];
+      const x1 = next
+        ? ((next.time - timeDifference) / zoomScale) * this.#width +
+          this.#panXoffset
+        : this.#width;
+      if (x1 < 0 || x > this.#width) return;
+
+      this.#ctx.beginPath();
+      this.#ctx.moveTo(x, toY(ratio));
+      this.#ctx.lineTo(x1, toY(ratio));
+      this.#ctx.lineWidth = 2;
+      this.#ctx.strokeStyle = hexToRgba(
+        ratio >= 1 ? this.#controller.colors.buy : this.#controller.colors.sell,
+        0.6
+      );
+      this.#ctx.stroke();
+    });
+    const latest = this.#lsRatios.at(-1).ratio;
+    this.#ctx.font = this.#controller.labelFont(-1);
+    this.#ctx.fillStyle = "#c8c8c8";
+    this.#ctx.fillText(`L/S ${latest.toFixed(2)}`, 5, 12);
+  }
+  // a strip along the bottom, klines without a depth sample are left empty
+  drawSpread(timeDifference, zoomScale) {
+    const points = [...this.#dataPoints, this.#currentDataPoint].filter(
+      ({ spread }) => spread !== null && spread !== undefined
+    );
+    if (points.length === 0) return;
+
+    const stripHeight = this.#height * 0.3;
+    const maxSpread = Math.max(...points.map(({ spread }) => spread));
+    this.#ctx.fillStyle = "rgba(200, 200, 200, 0.3)";
+    points.forEach(({ startTime, spread }) => {
+      const x =
+        ((startTime - timeDifference) / zoomScale) * this.#width +
+        this.#panXoffset;
+      if (x + this.#minuteWidth < 0 || x > this.#width) return;
+
+      const barHeight = maxSpread > 0 ? (spread / maxSpread) * stripHeight : 0;
+      this.#ctx.fillRect(
+        x + 1,
+        this.#height - barHeight,
+        this.#minuteWidth - 2,
+        barHeight
+      );
+    });
+    const latest = this.#currentDataPoint.spread ?? points.at(-1).spread;
+    this.#ctx.font = this.#controller.labelFont(-1);
+    this.#ctx.fillStyle = "#c8c8c8";
+    this.#ctx.fillText(
+      `spread ${latest.toFixed(2)} bps`,
+      5,
+      this.#height - stripHeight - 4
+    );
+  }
   drawCVDLine(x, y, x1, y1) {
     this.#ctx.beginPath();
     this.#ctx.moveTo(x, y);
@@ -1337,11 +2732,26 @@ class Canvas4 {
   }
   async fetchOI(symbol) {
     const response = await fetch(
-      `https://fapi.binance.com/fapi/v1/openInterest?symbol=${symbol}`
+      `${endpoints.rest}/fapi/v1/openInterest?symbol=${symbol}`
     );
     const data = await response.json();
     return data.openInterest;
   }
+  async fetchLongShortRatio(symbol) {
+    try {
+      const response = await fetch(
+        `${endpoints.rest}/futures/data/globalLongShortAccountRatio?symbol=${symbol}&period=5m&limit=30`
+      );
+      const data = await response.json();
+      return data.map((sample) => ({
+        time: sample.timestamp,
+        ratio: parseFloat(sample.longShortRatio),
+      }));
+    } catch (error) {
+      console.log(error, symbol);
+      return null;
+    }
+  }
 }
 class OverlayCanvas1 {
   #controller;
@@ -1351,6 +2761,7 @@ class OverlayCanvas1 {
   #yMin;
   #yMax;
   crosshairSelected = false;
+  ruler = null;
   constructor(controller, ctx, canvas, width, height) {
     this.#controller = controller;
     this.#ctx = ctx;
@@ -1359,11 +2770,26 @@ class OverlayCanvas1 {
     this.#height = height;
   }
   updateCrosshair(x, y) {
-    if (this.crosshairSelected) {
+    if (this.crosshairSelected || this.ruler) {
       this.#ctx.clearRect(0, 0, this.#width, this.#height);
-      this.drawCrosshair(x, y);
+      if (this.crosshairSelected) this.drawCrosshair(x, y);
+      if (this.ruler) this.drawRuler(this.ruler);
     }
   }
+  drawRuler({ start, end, label }) {
+    this.#ctx.fillStyle = "rgba(200, 200, 200, 0.08)";
+    this.#ctx.fillRect(start.x, start.y, end.x - start.x, end.y - start.y);
+
+    this.#ctx.beginPath();
+    this.#ctx.moveTo(start.x, start.y);
+    this.#ctx.lineTo(end.x, end.y);
+    this.#ctx.strokeStyle = "rgba(200, 200, 200, 0.7)";
+    this.#ctx.stroke();
+
+    this.#ctx.font = this.#controller.labelFont();
+    this.#ctx.fillStyle = "rgba(220, 220, 220, 0.9)";
+    this.#ctx.fillText(label, end.x + 8, end.y - 8);
+  }
   drawCrosshair(x, y) {
     this.#ctx.beginPath();
     this.#ctx.moveTo(x, 0);
@@ -1389,6 +2815,9 @@ class OverlayCanvas2 {
   bucketSize;
   #yMin;
   #yMax;
+  #anchorPrice;
+  #scaleMode;
+  #font;
   constructor(controller, ctx, canvas, width, height) {
     this.#controller = controller;
     this.#ctx = ctx;
@@ -1398,12 +2827,24 @@ class OverlayCanvas2 {
   }
 
   drawStart(yMin, yMax) {
-    if (yMin === this.#yMin && yMax === this.#yMax) return;
+    const { anchorPrice, priceScaleMode } = this.#controller;
+    const font = this.#controller.labelFont();
+    if (
+      yMin === this.#yMin &&
+      yMax === this.#yMax &&
+      anchorPrice === this.#anchorPrice &&
+      priceScaleMode === this.#scaleMode &&
+      font === this.#font
+    )
+      return;
 
     this.#ctx.clearRect(0, 0, this.#width, this.#height);
 
     this.#yMin = yMin;
     this.#yMax = yMax;
+    this.#anchorPrice = anchorPrice;
+    this.#scaleMode = priceScaleMode;
+    this.#font = font;
 
     const scaleFactor = this.#height / (yMax - yMin);
     const scalePoints = 20;
@@ -1414,11 +2855,15 @@ class OverlayCanvas2 {
         (Math.round(yValue / this.bucketSize) * this.bucketSize).toFixed(4)
       );
       const y = Math.round(this.#height - (yValue - yMin) * scaleFactor);
-      this.drawTextAt(y, Number(yValue.toFixed(4)), "#c8c8c8");
+      const text =
+        priceScaleMode === "percent" && anchorPrice
+          ? (((yValue - anchorPrice) / anchorPrice) * 100).toFixed(2) + "%"
+          : Number(yValue.toFixed(4));
+      this.drawTextAt(y, text, "#c8c8c8");
     }
   }
   drawTextAt(y, text, color) {
-    this.#ctx.font = "11px monospace";
+    this.#ctx.font = this.#controller.labelFont();
     this.#ctx.fillStyle = color;
     this.#ctx.fillText(text, 5, y);
   }
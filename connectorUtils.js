@@ -1,3 +1,5 @@
+import { endpoints } from "./endpoints.js";
+
 export async function combineDicts() {
   let currentTime = Date.now();
   let startTime = currentTime - 25 * 60 * 60 * 1000;
@@ -51,7 +53,7 @@ async function fetch_hist_OI(symbol, startTime, endTime) {
   try {
     current_OI = await fetch_current_OI(symbol);
     const response = await fetch(
-      `https://fapi.binance.com/futures/data/openInterestHist?symbol=${symbol}&period=30m&limit=1&startTime=${startTime}&endTime=${endTime}`
+      `${endpoints.rest}/futures/data/openInterestHist?symbol=${symbol}&period=30m&limit=1&startTime=${startTime}&endTime=${endTime}`
     );
     const data = await response.json();
 
@@ -68,7 +70,7 @@ async function fetch_hist_OI(symbol, startTime, endTime) {
 async function fetch_current_OI(symbol) {
   try {
     const response = await fetch(
-      `https://fapi.binance.com/fapi/v1/openInterest?symbol=${symbol}`
+      `${endpoints.rest}/fapi/v1/openInterest?symbol=${symbol}`
     );
     const data = await response.json();
     return Number(data["openInterest"]);
@@ -81,7 +83,7 @@ async function fetch_current_OI(symbol) {
 async function fetchPremiumIndexes() {
   let fr_dict = {};
 
-  const response = await fetch(`https://fapi.binance.com/fapi/v1/premiumIndex`);
+  const response = await fetch(`${endpoints.rest}/fapi/v1/premiumIndex`);
   const data = await response.json();
 
   for (let i of data) {
@@ -100,7 +102,7 @@ async function fetchPremiumIndexes() {
 async function fetch24hrMetrics() {
   let turnovers_dict = {};
 
-  const response = await fetch(`https://fapi.binance.com/fapi/v1/ticker/24hr`);
+  const response = await fetch(`${endpoints.rest}/fapi/v1/ticker/24hr`);
   const data = await response.json();
   for (let i of data) {
     let symbol = i["symbol"];
@@ -0,0 +1,43 @@
+const DEFAULT_ENDPOINTS = {
+  rest: "https://fapi.binance.com",
+  ws: "wss://fstream.binance.com",
+};
+const ALLOWED_PROTOCOLS = { rest: ["https:", "http:"], ws: ["wss:", "ws:"] };
+
+// hosts every request is built from, overrides come from localStorage
+export const endpoints = { ...DEFAULT_ENDPOINTS };
+Object.keys(DEFAULT_ENDPOINTS).forEach((kind) => {
+  const override = normalizeEndpoint(
+    kind,
+    localStorage.getItem(`endpoint-${kind}`)
+  );
+  if (override) endpoints[kind] = override;
+});
+
+export function defaultEndpoint(kind) {
+  return DEFAULT_ENDPOINTS[kind];
+}
+// an empty value restores the default, an invalid one is rejected
+export function setEndpoint(kind, value) {
+  if (!value) {
+    localStorage.removeItem(`endpoint-${kind}`);
+    endpoints[kind] = DEFAULT_ENDPOINTS[kind];
+    return true;
+  }
+  const endpoint = normalizeEndpoint(kind, value);
+  if (!endpoint) return false;
+
+  localStorage.setItem(`endpoint-${kind}`, endpoint);
+  endpoints[kind] = endpoint;
+  return true;
+}
+function normalizeEndpoint(kind, value) {
+  if (!value) return null;
+  try {
+    const url = new URL(value.trim());
+    if (!ALLOWED_PROTOCOLS[kind].includes(url.protocol)) return null;
+    return url.href.replace(/\/+$/, "");
+  } catch (error) {
+    return null;
+  }
+}
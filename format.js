@@ -0,0 +1,21 @@
+// "abbreviated" keeps the k/m/b suffixes, "full" prints every digit grouped
+export const numberFormat = { style: "abbreviated" };
+
+export function formatLargeNumber(num) {
+  if (numberFormat.style === "full") {
+    return num.toLocaleString(undefined, { maximumFractionDigits: 2 });
+  }
+  if (num >= 1.0e9) {
+    return (num / 1.0e9).toFixed(2) + "b";
+  } else if (num >= 1.0e6) {
+    return (num / 1.0e6).toFixed(2) + "m";
+  } else if (num >= 1.0e3) {
+    return (num / 1.0e3).toFixed(2) + "k";
+  } else {
+    return num;
+  }
+}
+// plain quantities on the chart are only grouped when full numbers are chosen
+export function formatQuantity(num) {
+  return numberFormat.style === "full" ? num.toLocaleString() : String(num);
+}
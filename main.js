@@ -1,10 +1,14 @@
 import { CanvasController } from "./canvasAggr.js";
 import { combineDicts } from "./connectorUtils.js";
 import { WebSocketService } from "./wsBinance.js";
+import { StreamRecorder } from "./recorder.js";
+import { showToast } from "./toast.js";
+import { numberFormat, formatLargeNumber } from "./format.js";
+import { endpoints, defaultEndpoint, setEndpoint } from "./endpoints.js";
 
 const buttons = ["btn1", "btn2", "btn3", "btn4"];
-const menuIds = ["tickers-menu", "menu2", "menu3", "settings-menu"];
-const functions = [showTickers, showMenu, showMenu, showSettings];
+const menuIds = ["tickers-menu", "menu2", "drawing-menu", "settings-menu"];
+const functions = [showTickers, showMenu, showDrawingMenu, showSettings];
 
 for (let i = 0; i < buttons.length; i++) {
   const button = document.getElementById(buttons[i]);
@@ -16,6 +20,57 @@ for (let i = 0; i < buttons.length; i++) {
 
 const tickersMenu = document.getElementById("tickers-menu");
 const settingsMenu = document.getElementById("settings-menu");
+const drawingMenu = document.getElementById("drawing-menu");
+const reconnectToggle = document.getElementById("reconnect-toggle");
+const streamTimeoutSelect = document.getElementById("stream-timeout-select");
+const updateIntervalSelect = document.getElementById("update-interval-select");
+const startupSelect = document.getElementById("startup-select");
+const keepTickersToggle = document.getElementById("keep-tickers-toggle");
+const numberFormatSelect = document.getElementById("number-format-select");
+
+numberFormatSelect.addEventListener("change", function () {
+  numberFormat.style = numberFormatSelect.value;
+  if (tickersData) {
+    generateTable(tickersData);
+  }
+});
+
+// host overrides, for regions or networks that need a mirror or relay
+["rest", "ws"].forEach((kind) => {
+  const hostInput = document.getElementById(`${kind}-host-input`);
+  hostInput.placeholder = defaultEndpoint(kind);
+  if (endpoints[kind] !== defaultEndpoint(kind)) {
+    hostInput.value = endpoints[kind];
+  }
+  hostInput.addEventListener("change", function () {
+    if (!setEndpoint(kind, hostInput.value.trim())) {
+      showToast(`${hostInput.value} isn't a valid ${kind} URL`, "error");
+      hostInput.value =
+        endpoints[kind] === defaultEndpoint(kind) ? "" : endpoints[kind];
+      return;
+    }
+    showToast(
+      `${kind} host set to ${endpoints[kind]}, used from the next request`
+    );
+  });
+});
+
+// startup behaviour, keeping the tickers open, the update interval, the hosts
+// above, the study defaults and the chart settings restored through
+// persistSetting are the settings kept across reloads
+startupSelect.value = localStorage.getItem("startupMode") ?? "blank";
+startupSelect.addEventListener("change", function () {
+  localStorage.setItem("startupMode", startupSelect.value);
+});
+keepTickersToggle.checked = localStorage.getItem("keepTickersOpen") === "true";
+keepTickersToggle.addEventListener("change", function () {
+  localStorage.setItem("keepTickersOpen", keepTickersToggle.checked);
+});
+window.addEventListener("beforeunload", function () {
+  if (activeTicker) {
+    localStorage.setItem("lastSetup", encodeSetup());
+  }
+});
 
 let input = document.getElementById("ticker-search");
 let searchTerm;
@@ -45,7 +100,9 @@ function canvasStarter(symbol, initialPrice) {
   for (let row of rows) {
     row.style.display = "";
   }
-  showTickers();
+  if (!keepTickersToggle.checked) {
+    showTickers();
+  }
 }
 
 function getCurrentTime() {
@@ -69,6 +126,8 @@ tickersUpdateBtn.addEventListener("click", function () {
     generateTable(data);
     updateLastUpdatedInfo();
     tickersUpdateBtn.disabled = false;
+
+    tickersData = data;
   });
 });
 
@@ -80,8 +139,19 @@ window.onload = function () {
     generateTable(data);
     updateLastUpdatedInfo();
     tickersUpdateBtn.disabled = false;
+
+    tickersData = data;
+    if (window.location.hash) {
+      applySetupFromHash();
+    } else if (startupSelect.value === "last") {
+      const lastSetup = localStorage.getItem("lastSetup");
+      if (lastSetup) applySetup(lastSetup);
+    } else if (startupSelect.value === "picker") {
+      showTickers();
+    }
   });
 };
+window.addEventListener("hashchange", applySetupFromHash);
 
 function showMenu() {
   console.log("show menu");
@@ -116,8 +186,20 @@ function showSettings() {
     document.removeEventListener("click", closeMenu);
   }
 }
+function showDrawingMenu() {
+  drawingMenu.style.display =
+    drawingMenu.style.display === "none" ? "block" : "none";
+  updateButtonState("btn3", "drawing-menu");
+
+  if (drawingMenu.style.display === "block") {
+    document.addEventListener("click", closeMenu);
+  } else {
+    document.removeEventListener("click", closeMenu);
+  }
+}
 function closeMenu(e) {
   const btn1 = document.querySelector("#btn1");
+  const btn3 = document.querySelector("#btn3");
   const btn4 = document.querySelector("#btn4");
 
   if (!settingsMenu.contains(e.target) && !btn4.contains(e.target)) {
@@ -128,10 +210,15 @@ function closeMenu(e) {
     tickersMenu.style.display = "none";
     updateButtonState("btn1", "tickers-menu");
   }
+  if (!drawingMenu.contains(e.target) && !btn3.contains(e.target)) {
+    drawingMenu.style.display = "none";
+    updateButtonState("btn3", "drawing-menu");
+  }
 
   if (
     settingsMenu.style.display === "none" &&
-    tickersMenu.style.display === "none"
+    tickersMenu.style.display === "none" &&
+    drawingMenu.style.display === "none"
   ) {
     document.removeEventListener("click", closeMenu);
   }
@@ -141,7 +228,7 @@ function updateButtonState(buttonId, menuId) {
   const menu = document.getElementById(menuId);
   const button = document.getElementById(buttonId);
 
-  if (buttonId === "btn1" || buttonId === "btn4") {
+  if (buttonId === "btn1" || buttonId === "btn3" || buttonId === "btn4") {
     if (menu.style.display === "block") {
       button.classList.add("active");
     } else {
@@ -150,17 +237,6 @@ function updateButtonState(buttonId, menuId) {
   }
 }
 
-function formatLargeNumber(num) {
-  if (num >= 1.0e9) {
-    return (num / 1.0e9).toFixed(2) + "b";
-  } else if (num >= 1.0e6) {
-    return (num / 1.0e6).toFixed(2) + "m";
-  } else if (num >= 1.0e3) {
-    return (num / 1.0e3).toFixed(2) + "k";
-  } else {
-    return num;
-  }
-}
 function formatNumber(value, type, price) {
   let displayValue;
 
@@ -247,6 +323,105 @@ function generateTable(data) {
   }
 }
 
+// Shareable setup links
+let tickersData = null;
+const INDICATOR_BUTTONS = {
+  cvd: "cvdToggleBtn",
+  oi: "oiToggleBtn",
+  mark: "markToggleBtn",
+  index: "indexToggleBtn",
+  ls: "lsToggleBtn",
+  spread: "spreadToggleBtn",
+};
+// number keys toggle the indicators in top bar order, 1 for cvd and so on
+document.addEventListener("keydown", function (event) {
+  const target = event.target;
+  if (
+    event.ctrlKey ||
+    event.metaKey ||
+    event.altKey ||
+    target.isContentEditable ||
+    ["INPUT", "SELECT", "TEXTAREA"].includes(target.tagName)
+  ) {
+    return;
+  }
+  const buttonId = Object.values(INDICATOR_BUTTONS)[Number(event.key) - 1];
+  if (buttonId) {
+    document.getElementById(buttonId).click();
+  }
+});
+function isIndicatorActive(indicator) {
+  const button = document.getElementById(INDICATOR_BUTTONS[indicator]);
+  return !button.classList.contains("disabled");
+}
+function encodeSetup() {
+  const setup = {
+    s: activeTicker.symbol,
+    t: Number(tickSizeSelect.value),
+    i: Object.keys(INDICATOR_BUTTONS).filter(isIndicatorActive),
+  };
+  return btoa(JSON.stringify(setup));
+}
+function decodeSetup(encoded) {
+  const setup = JSON.parse(atob(encoded));
+  const multipliers = Array.from(tickSizeSelect.options).map((option) =>
+    Number(option.value)
+  );
+  if (
+    typeof setup.s !== "string" ||
+    !/^[A-Z0-9]+$/.test(setup.s) ||
+    !multipliers.includes(setup.t) ||
+    !Array.isArray(setup.i) ||
+    !setup.i.every((indicator) => indicator in INDICATOR_BUTTONS)
+  ) {
+    throw new Error("invalid setup: " + encoded);
+  }
+  return setup;
+}
+function applySetupFromHash() {
+  const match = window.location.hash.match(/^#setup=(.+)$/);
+  if (!match || !tickersData) {
+    return;
+  }
+  applySetup(decodeURIComponent(match[1]));
+}
+function applySetup(encoded) {
+  let setup;
+  try {
+    setup = decodeSetup(encoded);
+  } catch (error) {
+    console.error(error);
+    showToast("This setup link is malformed and couldn't be loaded", "error");
+    return;
+  }
+  if (!tickersData[setup.s]) {
+    showToast(`${setup.s} isn't listed on Binance Futures`, "error");
+    return;
+  }
+  Object.keys(INDICATOR_BUTTONS).forEach((indicator) => {
+    if (isIndicatorActive(indicator) !== setup.i.includes(indicator)) {
+      document.getElementById(INDICATOR_BUTTONS[indicator]).click();
+    }
+  });
+  userTickMultipliers[setup.s] = String(setup.t);
+  startCanvas(setup.s, tickersData[setup.s].mark_price);
+}
+document.getElementById("copy-setup-btn").addEventListener("click", () => {
+  if (!activeTicker) {
+    return;
+  }
+  const url = `${window.location.origin}${
+    window.location.pathname
+  }#setup=${encodeURIComponent(encodeSetup())}`;
+  navigator.clipboard
+    .writeText(url)
+    .then(() => showToast("Setup link copied to clipboard"))
+    .catch((error) => {
+      console.error("Error copying setup link:", error);
+      showToast("Couldn't copy the setup link", "error");
+    });
+});
+
 function resizeCanvasToDisplaySize(canvas) {
   const width = canvas.clientWidth;
   const height = canvas.clientHeight;
@@ -287,24 +462,325 @@ let canvasObjects = canvasData.map((data) => {
 });
 const webSocketService = new WebSocketService();
 const MainCanvas = new CanvasController(canvasObjects);
+const streamRecorder = new StreamRecorder();
+
+// applies a saved value through the control's own listener, so the chart
+// takes it up like a user change, values no longer offered are dropped
+function restoreSetting(control, saved) {
+  const eventName = control.type === "color" ? "input" : "change";
+  if (control.type === "checkbox") {
+    if (typeof saved !== "boolean") return;
+    control.checked = saved;
+  } else {
+    const previous = control.value;
+    control.value = saved;
+    if (control.value !== String(saved)) {
+      control.value = previous;
+      return;
+    }
+  }
+  control.dispatchEvent(new Event(eventName));
+}
+function persistSetting(id, key) {
+  const control = document.getElementById(id);
+  const eventName = control.type === "color" ? "input" : "change";
+  const saved = localStorage.getItem(key);
+  if (saved !== null) restoreSetting(control, saved);
+  control.addEventListener(eventName, function () {
+    localStorage.setItem(key, control.value);
+  });
+}
+persistSetting("buy-color", "buyColor");
+persistSetting("sell-color", "sellColor");
+persistSetting("last-price-style-select", "lastPriceStyle");
+persistSetting("last-price-width-select", "lastPriceWidth");
+persistSetting("last-price-color", "lastPriceColor");
+persistSetting("oi-alert-select", "oiAlertThreshold");
+persistSetting("oi-alert-window-select", "oiAlertWindow");
+persistSetting("large-trade-select", "largeTradeThreshold");
+persistSetting("break-even-select", "breakEvenBps");
+persistSetting("price-scale-select", "priceScaleMode");
+persistSetting("font-size-select", "labelFontSize");
+persistSetting("font-family-select", "labelFontFamily");
+persistSetting("fib-levels-select", "fibLevels");
+
+// footprint study settings, saved ones are applied on every load
+const STUDY_SETTINGS = [
+  "min-study-trades-select",
+  "poc-line-toggle",
+  "poc-color",
+  "value-area-select",
+  "highlight-top-select",
+  "highlight-percentile-select",
+];
+let studyDefaults = {};
+try {
+  studyDefaults = JSON.parse(localStorage.getItem("studyDefaults") ?? "{}");
+} catch (error) {
+  console.error("Ignoring unreadable study defaults:", error);
+}
+STUDY_SETTINGS.forEach((id) => {
+  if (id in studyDefaults) {
+    restoreSetting(document.getElementById(id), studyDefaults[id]);
+  }
+});
+document
+  .getElementById("save-studies-btn")
+  .addEventListener("click", function () {
+    const defaults = Object.fromEntries(
+      STUDY_SETTINGS.map((id) => {
+        const control = document.getElementById(id);
+        const value =
+          control.type === "checkbox" ? control.checked : control.value;
+        return [id, value];
+      })
+    );
+    localStorage.setItem("studyDefaults", JSON.stringify(defaults));
+    showToast("current studies saved as the default");
+  });
 
-function startCanvas(symbol, initialPrice) {
-  fetchExchangeInfo(symbol).then(([tickSize, minQty]) => {
-    // start websocket, send the data to the controller as it arrives
-    webSocketService.createWebSocket(symbol, (data) =>
-      MainCanvas.updateData(data)
+const recordBtn = document.getElementById("recordBtn");
+const recordSize = document.getElementById("record-size");
+recordBtn.addEventListener("click", function () {
+  if (streamRecorder.isRecording) {
+    streamRecorder.stop();
+  } else if (activeTicker) {
+    streamRecorder.start(
+      activeTicker.symbol,
+      document.getElementById("record-depth-toggle").checked
     );
+  }
+  updateRecordState();
+});
+function updateRecordState() {
+  recordBtn.classList.toggle("disabled", !streamRecorder.isRecording);
+  recordBtn.classList.toggle("recording", streamRecorder.isRecording);
+  recordSize.textContent = streamRecorder.isRecording
+    ? formatBytes(streamRecorder.size)
+    : "";
+}
+function formatBytes(bytes) {
+  if (bytes >= 1.0e6) {
+    return (bytes / 1.0e6).toFixed(1) + " MB";
+  } else if (bytes >= 1.0e3) {
+    return (bytes / 1.0e3).toFixed(1) + " KB";
+  } else {
+    return bytes + " B";
+  }
+}
+
+let activeTicker = null;
+
+// roughly how many price levels should fit the default zoom, which spans ~0.6% of price
+const TARGET_PRICE_LEVELS = 60;
+const userTickMultipliers = {};
+
+const tickSizeSelect = document.querySelector("#ticksize-select");
+tickSizeSelect.addEventListener("change", function (event) {
+  if (event.isTrusted && activeTicker) {
+    userTickMultipliers[activeTicker.symbol] = this.value;
+  }
+});
+function pickTickMultiplier(symbol, tickSize, price) {
+  if (userTickMultipliers[symbol]) {
+    return userTickMultipliers[symbol];
+  }
+  if (!price || !tickSize) {
+    return "5";
+  }
+  const visibleRange = price * 0.006;
+  const multipliers = Array.from(tickSizeSelect.options).map((option) =>
+    Number(option.value)
+  );
+  const multiplier = multipliers.find(
+    (m) => visibleRange / (tickSize * m) <= TARGET_PRICE_LEVELS
+  );
+  return String(multiplier ?? multipliers[multipliers.length - 1]);
+}
 
-    MainCanvas.startNew(symbol, tickSize, minQty, initialPrice);
-    document
-      .querySelector("#ticksize-select")
-      .dispatchEvent(new Event("change"));
-    document.querySelector("#tickerInfo-name").textContent = symbol;
+// streams die while the machine sleeps, restart them and refetch the recent klines on wake
+document.addEventListener("visibilitychange", function () {
+  if (
+    document.visibilityState !== "visible" ||
+    !activeTicker ||
+    pendingStarts > 0
+  ) {
+    return;
+  }
+  if (reconnectToggle.checked && webSocketService.isStale()) {
+    console.log("stream went stale, reconnecting:", activeTicker.symbol);
+    startCanvas(activeTicker.symbol, activeTicker.initialPrice);
+  }
+});
+
+// browsers can't send ping frames, treat a silent stream as a dropped one,
+// restarts back off while they bring no data and the toast shows once per
+// outage
+let streamWatchdog;
+let restartDelay = 0;
+let nextRestartAt = 0;
+let outageNotified = false;
+function resetStreamBackoff() {
+  restartDelay = 0;
+  nextRestartAt = 0;
+  outageNotified = false;
+}
+streamTimeoutSelect.addEventListener("change", function () {
+  clearInterval(streamWatchdog);
+  const timeout = Number(streamTimeoutSelect.value);
+  if (!timeout) {
+    return;
+  }
+  streamWatchdog = setInterval(() => {
+    if (
+      document.visibilityState !== "visible" ||
+      !activeTicker ||
+      pendingStarts > 0
+    ) {
+      return;
+    }
+    if (webSocketService.isStale(timeout) && Date.now() >= nextRestartAt) {
+      if (!outageNotified) {
+        outageNotified = true;
+        showToast(
+          `no data from the stream for ${timeout / 1000}s, reconnecting`,
+          "warn"
+        );
+      }
+      // the first retry waits the timeout, each one after doubles, up to 5m
+      restartDelay = Math.min(
+        restartDelay ? restartDelay * 2 : timeout,
+        300000
+      );
+      nextRestartAt = Date.now() + restartDelay;
+      startCanvas(activeTicker.symbol, activeTicker.initialPrice);
+    }
+  }, 1000);
+});
+streamTimeoutSelect.dispatchEvent(new Event("change"));
+
+// depth updates arrive every 100ms, so that is also the fastest redraw
+updateIntervalSelect.value = localStorage.getItem("updateInterval") ?? "100";
+updateIntervalSelect.addEventListener("change", function () {
+  webSocketService.updateInterval = Math.max(
+    100,
+    Number(updateIntervalSelect.value)
+  );
+  localStorage.setItem("updateInterval", updateIntervalSelect.value);
+});
+updateIntervalSelect.dispatchEvent(new Event("change"));
+
+// nothing is fixed here, the full list goes to the console for bug reports
+document
+  .getElementById("check-data-btn")
+  .addEventListener("click", function () {
+    if (!activeTicker) return;
+
+    const { stale, resyncs } = webSocketService.depthIntegrity;
+    const findings = MainCanvas.integrityReport();
+    if (stale > 0) {
+      findings.push(`${stale} depth updates older than the book were dropped`);
+    }
+    if (resyncs > 0) {
+      findings.push(`depth update ids skipped ${resyncs} times, book resynced`);
+    }
+    console.log(`data check for ${activeTicker.symbol}:`, findings);
+    if (findings.length === 0) {
+      showToast("data check: no issues found");
+    } else {
+      showToast(
+        `data check: ${findings.length} issues, ${findings[0]}` +
+          (findings.length > 1 ? ", full list in the console" : ""),
+        "warn"
+      );
+    }
   });
+
+// per second rates from the websocket's running totals, breakdown on hover,
+// latency is the mean over the last second
+const streamStatsInfo = document.getElementById("stream-stats");
+let lastStreamTotals = {};
+setInterval(() => {
+  const stats = webSocketService.streamStats;
+  const rates = Object.entries(stats).map(
+    ([stream, { messages, bytes, latency }]) => {
+      const last = lastStreamTotals[stream] ?? {
+        messages: 0,
+        bytes: 0,
+        latency: 0,
+      };
+      const newMessages = Math.max(0, messages - last.messages);
+      return {
+        stream,
+        messages: newMessages,
+        bytes: Math.max(0, bytes - last.bytes),
+        latency: newMessages
+          ? Math.round((latency - last.latency) / newMessages)
+          : null,
+      };
+    }
+  );
+  lastStreamTotals = structuredClone(stats);
+
+  const totalBytes = rates.reduce((sum, rate) => sum + rate.bytes, 0);
+  const depthLatency =
+    rates.find(({ stream }) => stream === "depth@100ms")?.latency ?? null;
+  streamStatsInfo.textContent = rates.length
+    ? `${formatBytes(totalBytes)}/s` +
+      (depthLatency !== null ? `, ${depthLatency}ms` : "")
+    : "";
+  streamStatsInfo.title = rates
+    .map(
+      ({ stream, messages, bytes, latency }) =>
+        `${stream}: ${messages} msg/s, ${formatBytes(bytes)}/s, ${formatBytes(
+          stats[stream].bytes
+        )} total` + (latency !== null ? `, ${latency}ms behind` : "")
+    )
+    .concat("latency is exchange event time to local receive time")
+    .join("\n");
+}, 1000);
+
+// starts waiting on exchangeInfo, stale checks leave the stream alone meanwhile
+// since a new websocket is on its way
+let pendingStarts = 0;
+function startCanvas(symbol, initialPrice) {
+  activeTicker = { symbol, initialPrice };
+  pendingStarts++;
+
+  if (streamRecorder.isRecording && streamRecorder.symbol !== symbol) {
+    streamRecorder.stop();
+    updateRecordState();
+  }
+
+  fetchExchangeInfo(symbol)
+    .then(([tickSize, minQty]) => {
+      // start websocket, send the data to the controller as it arrives
+      webSocketService.createWebSocket(symbol, (data) => {
+        resetStreamBackoff();
+        MainCanvas.updateData(data);
+
+        if (streamRecorder.isRecording) {
+          streamRecorder.record(data);
+          updateRecordState();
+        }
+      });
+
+      MainCanvas.startNew(symbol, tickSize, minQty, initialPrice);
+      tickSizeSelect.value = pickTickMultiplier(
+        symbol,
+        Number(tickSize),
+        initialPrice
+      );
+      tickSizeSelect.dispatchEvent(new Event("change"));
+      document.querySelector("#tickerInfo-name").textContent = symbol;
+    })
+    .finally(() => {
+      pendingStarts--;
+    });
 }
 
 async function fetchExchangeInfo(symbol) {
-  const response = await fetch(`https://fapi.binance.com/fapi/v1/exchangeInfo`);
+  const response = await fetch(`${endpoints.rest}/fapi/v1/exchangeInfo`);
   const data = await response.json();
 
   let symbol_info = data["symbols"].find((x) => x.symbol === symbol);
@@ -0,0 +1,84 @@
+import { showToast } from "./toast.js";
+
+// depth adds a full book every 100ms, stop before the tab runs out of memory
+const RECORDING_MAX_MB = 256;
+
+export class StreamRecorder {
+  #lines = [];
+  #size = 0;
+  #symbol;
+  #startTime;
+  #withDepth = false;
+  isRecording = false;
+  constructor() {
+    console.log("Initializing StreamRecorder");
+  }
+
+  get symbol() {
+    return this.#symbol;
+  }
+  get size() {
+    return this.#size;
+  }
+  start(symbol, withDepth) {
+    this.#lines = [];
+    this.#size = 0;
+    this.#symbol = symbol;
+    this.#startTime = Date.now();
+    this.#withDepth = withDepth;
+    this.isRecording = true;
+    console.log("started recording", symbol, withDepth ? "with depth" : "");
+  }
+  record(data) {
+    if (!this.isRecording) return;
+
+    data.tradesBuffer.forEach((trade) => {
+      this.#push({
+        type: "trade",
+        time: trade.x,
+        price: trade.y,
+        qty: trade.q,
+        is_buyer_maker: trade.m,
+      });
+    });
+    if (this.#withDepth && data.depth) {
+      this.#push({
+        type: "depth",
+        time: Date.now(),
+        bids: data.depth.bids,
+        asks: data.depth.asks,
+      });
+    }
+  }
+  #push(entry) {
+    if (!this.isRecording) return;
+
+    const line = JSON.stringify(entry) + "\n";
+    this.#lines.push(line);
+    this.#size += line.length;
+
+    if (this.#size >= RECORDING_MAX_MB * 1024 * 1024) {
+      this.stop();
+      showToast(
+        `recording reached ${RECORDING_MAX_MB}MB, stopped and saved`,
+        "warn"
+      );
+    }
+  }
+  // hands the recorded lines to the browser as a newline-delimited JSON download
+  stop() {
+    if (!this.isRecording) return;
+    this.isRecording = false;
+
+    const blob = new Blob(this.#lines, { type: "application/x-ndjson" });
+    const url = URL.createObjectURL(blob);
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = `${this.#symbol}_${this.#startTime}.ndjson`;
+    link.click();
+    setTimeout(() => URL.revokeObjectURL(url), 0);
+
+    console.log("stopped recording", this.#symbol, this.#size, "bytes");
+    this.#lines = [];
+  }
+}
@@ -0,0 +1,32 @@
+const TOAST_DURATION = 6000;
+// oldest toasts make room once this many are on screen
+const MAX_TOASTS = 5;
+let audioContext;
+
+export function showToast(message, level = "info") {
+  const container = document.getElementById("toast-container");
+  while (container.children.length >= MAX_TOASTS) {
+    container.firstElementChild.remove();
+  }
+  const toast = document.createElement("div");
+  toast.className = `toast ${level}`;
+  toast.textContent = message;
+  container.appendChild(toast);
+
+  setTimeout(() => toast.remove(), TOAST_DURATION);
+}
+
+export function playAlertSound() {
+  audioContext ??= new AudioContext();
+  const now = audioContext.currentTime;
+
+  const oscillator = audioContext.createOscillator();
+  const gain = audioContext.createGain();
+  oscillator.frequency.value = 880;
+  gain.gain.setValueAtTime(0.15, now);
+  gain.gain.exponentialRampToValueAtTime(0.001, now + 0.4);
+
+  oscillator.connect(gain).connect(audioContext.destination);
+  oscillator.start(now);
+  oscillator.stop(now + 0.4);
+}
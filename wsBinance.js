@@ -1,9 +1,20 @@
+import { showToast } from "./toast.js";
+import { endpoints } from "./endpoints.js";
+
 export class WebSocketService {
   #socket;
   #aggTradeBuffer = [];
   #klineBuffer = [];
+  #markPrice = null;
   #lowercaseSymbol;
   #is_first_event = true;
+  #lastMessageTime = 0;
+  #snapshotTimer = null;
+  #snapshotRetryDelay = 0;
+  #streamStats = {};
+  #depthIntegrity = { stale: 0, resyncs: 0 };
+  #lastCallbackTime = 0;
+  updateInterval = 100;
   last_update_id;
   order_book;
   constructor() {
@@ -11,30 +22,47 @@ export class WebSocketService {
   }
 
   createWebSocket(symbol, callback) {
-    if (this.#socket && this.#socket.readyState === 1) {
-      console.log(
-        "Closing existing websocket connection for symbol:",
-        this.#lowercaseSymbol.toUpperCase()
-      );
-      this.order_book.shouldRefresh = false;
-      this.#socket.close();
+    if (this.#socket) {
+      if (this.#socket.readyState <= 1) {
+        console.log(
+          "Closing existing websocket connection for symbol:",
+          this.#lowercaseSymbol.toUpperCase()
+        );
+        this.#socket.close();
+      }
+      if (this.order_book) this.order_book.shouldRefresh = false;
 
       this.#is_first_event = true;
       this.order_book = null;
       this.#aggTradeBuffer = [];
       this.#klineBuffer = [];
+      this.#markPrice = null;
     }
+    this.#streamStats = {};
+    this.#lastCallbackTime = 0;
+    this.#depthIntegrity = { stale: 0, resyncs: 0 };
     console.log("Creating websocket connection for symbol:", symbol);
     this.#lowercaseSymbol = symbol.toLowerCase();
+    // give the new connection the full timeout before it counts as stale
+    this.#lastMessageTime = Date.now();
+    clearTimeout(this.#snapshotTimer);
+    this.#snapshotTimer = null;
+    this.#snapshotRetryDelay = 0;
 
     fetchOrderbook(this.#lowercaseSymbol)
+      .catch((error) => {
+        // start from an empty book until the scheduled retry gets a snapshot
+        showToast("couldn't fetch the orderbook snapshot, retrying", "warn");
+        this.#scheduleSnapshotRetry(this.#lowercaseSymbol, error);
+        return { lastUpdateId: 0, bids: [], asks: [] };
+      })
       .then((depth_snapshot) => {
         this.#socket = new WebSocket(
-          `wss://fstream.binance.com/stream?streams=${
+          `${endpoints.ws}/stream?streams=${
             this.#lowercaseSymbol
           }@aggTrade/${this.#lowercaseSymbol}@depth@100ms/${
             this.#lowercaseSymbol
-          }@kline_1m`
+          }@kline_1m/${this.#lowercaseSymbol}@markPrice@1s`
         );
         this.setupEventListeners(this.#socket, callback);
 
@@ -44,11 +72,22 @@ export class WebSocketService {
           depth_snapshot.bids,
           depth_snapshot.asks
         );
-      })
-      .catch((error) => {
-        console.error("Error initializing the order book:", error);
       });
   }
+  // measured from the last message or from createWebSocket, so a socket still
+  // connecting, or one refused straight away, gets maxAge before it's stale
+  isStale(maxAge = 5000) {
+    return Date.now() - this.#lastMessageTime > maxAge;
+  }
+  // message, byte and latency totals per stream since the connection was
+  // created
+  get streamStats() {
+    return this.#streamStats;
+  }
+  // depth updates dropped for going backwards and resyncs after an id gap
+  get depthIntegrity() {
+    return this.#depthIntegrity;
+  }
   setupEventListeners(socket, callback) {
     socket.addEventListener("open", () => {
       this.order_book.refresh_order_book(this.#lowercaseSymbol);
@@ -60,8 +99,20 @@ export class WebSocketService {
 
     let isHandlingDepth = false;
     socket.addEventListener("message", async (event) => {
+      this.#lastMessageTime = Date.now();
       let message = JSON.parse(event.data);
 
+      const streamName = message.stream.split("@").slice(1).join("@");
+      const stats = (this.#streamStats[streamName] ??= {
+        messages: 0,
+        bytes: 0,
+        latency: 0,
+      });
+      stats.messages++;
+      stats.bytes += event.data.length;
+      // exchange event time to local receive time, includes any clock skew
+      stats.latency += this.#lastMessageTime - message.data.E;
+
       if (message.stream.endsWith("@aggTrade")) {
         let aggtradeStream = message.data;
         this.#aggTradeBuffer.push({
@@ -76,27 +127,52 @@ export class WebSocketService {
           return;
         }
         isHandlingDepth = true;
-        await this.handleDepth(message.data);
-
-        isHandlingDepth = false;
+        try {
+          await this.handleDepth(message.data);
+        } catch (error) {
+          console.error("Error handling depth update:", error);
+          return;
+        } finally {
+          isHandlingDepth = false;
+        }
+        // the book stays current, trades wait in the buffer for the next call,
+        // half an update of slack keeps arrival jitter from skipping redraws
+        if (
+          this.updateInterval > 100 &&
+          Date.now() - this.#lastCallbackTime < this.updateInterval - 50
+        ) {
+          return;
+        }
+        this.#lastCallbackTime = Date.now();
 
         callback({
           kline: this.#klineBuffer,
           depth: this.order_book.order_book,
           tradesBuffer: this.#aggTradeBuffer,
+          markPrice: this.#markPrice,
         });
         this.#aggTradeBuffer = [];
       } else if (message.stream.endsWith("@kline_1m")) {
         this.#klineBuffer = message.data;
+      } else if (message.stream.endsWith("@markPrice@1s")) {
+        this.#markPrice = {
+          mark: parseFloat(message.data.p),
+          index: parseFloat(message.data.i),
+        };
       }
     });
   }
   async handleDepth(depthStream) {
+    // the book can't be synced before the retried snapshot arrives
+    if (this.#snapshotTimer) return;
+
     let finalUpdateId = depthStream.u;
     let firstUpdateId = depthStream.U;
     let previousFinalUpdateId = depthStream.pu;
 
     if (finalUpdateId < this.last_update_id) {
+      // expected while catching up to the snapshot, a problem after that
+      if (!this.#is_first_event) this.#depthIntegrity.stale++;
       console.log(
         "finalUpdateId < last_update_id",
         finalUpdateId,
@@ -116,6 +192,7 @@ export class WebSocketService {
         return;
       }
     } else if (previousFinalUpdateId != this.last_update_id) {
+      this.#depthIntegrity.resyncs++;
       await this.reinitializeOrderBook(this.#lowercaseSymbol);
       return;
     }
@@ -125,7 +202,18 @@ export class WebSocketService {
   }
   async reinitializeOrderBook(symbol) {
     console.log("Out of sync, reinitializing order book...");
-    const depth_snapshot = await fetchOrderbook(symbol);
+    let depth_snapshot;
+    try {
+      depth_snapshot = await fetchOrderbook(symbol);
+    } catch (error) {
+      this.#scheduleSnapshotRetry(symbol, error);
+      return;
+    }
+    if (symbol !== this.#lowercaseSymbol || !this.order_book) return;
+
+    this.#snapshotRetryDelay = 0;
+    // a new snapshot needs the first event check again to resume the diffs
+    this.#is_first_event = true;
     this.last_update_id = depth_snapshot.lastUpdateId;
     this.order_book.order_book = this.order_book.initialize_order_book(
       depth_snapshot.bids,
@@ -133,6 +221,25 @@ export class WebSocketService {
     );
     this.#aggTradeBuffer = [];
   }
+  // a single pending retry with backoff, refetching on every depth event
+  // would turn a rate limit into an IP ban
+  #scheduleSnapshotRetry(symbol, error) {
+    this.#snapshotRetryDelay = Math.min(
+      Math.max(this.#snapshotRetryDelay * 2, 1000),
+      60000
+    );
+    const delay = error.retryAfter
+      ? error.retryAfter * 1000
+      : this.#snapshotRetryDelay;
+    console.error(`Orderbook snapshot failed, retrying in ${delay}ms:`, error);
+
+    const timer = setTimeout(() => {
+      this.reinitializeOrderBook(symbol).finally(() => {
+        if (this.#snapshotTimer === timer) this.#snapshotTimer = null;
+      });
+    }, delay);
+    this.#snapshotTimer = timer;
+  }
 }
 
 class OrderBook {
@@ -231,8 +338,16 @@ class OrderBook {
 
 async function fetchOrderbook(symbol) {
   const response = await fetch(
-    `https://fapi.binance.com/fapi/v1/depth?symbol=${symbol}&limit=500`
+    `${endpoints.rest}/fapi/v1/depth?symbol=${symbol}&limit=500`
   );
+  if (!response.ok) {
+    const error = new Error(
+      `depth snapshot request failed: ${response.status}`
+    );
+    // only readable when the proxy exposes it, the backoff covers the rest
+    error.retryAfter = Number(response.headers.get("Retry-After")) || 0;
+    throw error;
+  }
   const data = await response.json();
   return data;
 }